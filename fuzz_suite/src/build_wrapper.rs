@@ -0,0 +1,299 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::SystemTime;
+
+use walkdir::WalkDir;
+
+use super::compiler::CompilerConfig;
+use super::error::CompileError;
+
+/// Placeholders replaced by the wrapper paths in build commands
+const CC_PLACEHOLDER: &str = "{cc}";
+const CXX_PLACEHOLDER: &str = "{cxx}";
+
+/// Extensions of executable-looking files that are never the fuzz target
+const NON_TARGET_EXTENSIONS: &[&str] = &["o", "a", "so", "dylib", "sh", "py", "pl", "cmake"];
+
+/// An existing build system run with its compilers replaced by instrumenting wrappers
+#[derive(Debug, Clone)]
+pub struct WrappedBuild {
+    pub working_dir: PathBuf,
+    /// Commands run in order; `{cc}` and `{cxx}` are replaced by the wrapper paths
+    pub steps: Vec<Vec<String>>,
+    /// Expected binary, relative to `working_dir`, or just its file name
+    pub binary: Option<PathBuf>,
+}
+
+impl WrappedBuild {
+    pub fn new(working_dir: &Path, command: Vec<String>) -> Self {
+        WrappedBuild {
+            working_dir: working_dir.to_path_buf(),
+            steps: vec![command],
+            binary: None,
+        }
+    }
+
+    /// Run `make`, overriding `CC`/`CXX` on the command line so Makefile assignments lose
+    pub fn make(working_dir: &Path) -> Self {
+        WrappedBuild::new(
+            working_dir,
+            vec![
+                "make".to_string(),
+                format!("CC={}", CC_PLACEHOLDER),
+                format!("CXX={}", CXX_PLACEHOLDER),
+            ],
+        )
+    }
+
+    /// Configure and build a CMake project out of tree
+    pub fn cmake(source_dir: &Path, build_dir: &Path) -> Self {
+        let build = build_dir.to_string_lossy().into_owned();
+        WrappedBuild {
+            working_dir: build_dir.to_path_buf(),
+            steps: vec![
+                vec![
+                    "cmake".to_string(),
+                    "-S".to_string(),
+                    source_dir.to_string_lossy().into_owned(),
+                    "-B".to_string(),
+                    build.clone(),
+                    format!("-DCMAKE_C_COMPILER={}", CC_PLACEHOLDER),
+                    format!("-DCMAKE_CXX_COMPILER={}", CXX_PLACEHOLDER),
+                ],
+                vec!["cmake".to_string(), "--build".to_string(), build],
+            ],
+            binary: None,
+        }
+    }
+
+    /// Add another command to run after the previous ones
+    pub fn then(mut self, command: Vec<String>) -> Self {
+        self.steps.push(command);
+        self
+    }
+
+    /// Set where the produced binary is expected
+    pub fn with_binary(mut self, binary: &Path) -> Self {
+        self.binary = Some(binary.to_path_buf());
+        self
+    }
+
+    /// Run the build with instrumenting wrappers written to `wrapper_dir` and return the
+    /// path of the produced binary
+    pub fn build(
+        &self,
+        compiler: &CompilerConfig,
+        wrapper_dir: &Path,
+    ) -> Result<PathBuf, CompileError> {
+        compiler.validate()?;
+        fs::create_dir_all(&self.working_dir)?;
+        let (cc, cxx) = write_compiler_wrappers(compiler, wrapper_dir)?;
+        let cc = cc.to_string_lossy().into_owned();
+        let cxx = cxx.to_string_lossy().into_owned();
+
+        // File timestamps come from a coarser clock than SystemTime::now, so take the
+        // start time from a file written just before the build
+        let marker = wrapper_dir.join(".build-started");
+        fs::write(&marker, "")?;
+        let started = fs::metadata(&marker)?.modified()?;
+
+        for step in &self.steps {
+            let Some((program, args)) = step.split_first() else {
+                continue;
+            };
+            let args: Vec<String> = args
+                .iter()
+                .map(|a| a.replace(CC_PLACEHOLDER, &cc).replace(CXX_PLACEHOLDER, &cxx))
+                .collect();
+
+            // The wrappers add every flag, so CFLAGS and LDFLAGS are left to the build
+            let status = Command::new(program)
+                .args(&args)
+                .current_dir(&self.working_dir)
+                .env("CC", &cc)
+                .env("CXX", &cxx)
+                .status()?;
+
+            if !status.success() {
+                return Err(CompileError::BuildCommandFailed {
+                    command: step.join(" "),
+                    status,
+                });
+            }
+        }
+
+        Ok(self.locate_binary(started, wrapper_dir)?)
+    }
+
+    /// Find the binary produced by the build
+    fn locate_binary(&self, started: SystemTime, wrapper_dir: &Path) -> io::Result<PathBuf> {
+        if let Some(binary) = &self.binary {
+            let path = self.working_dir.join(binary);
+            if path.is_file() {
+                return Ok(path);
+            }
+        }
+
+        let wanted_name = self.binary.as_ref().and_then(|b| b.file_name());
+        let wrapper_dir = std::path::absolute(wrapper_dir)?;
+        let mut candidates: Vec<(SystemTime, PathBuf)> = WalkDir::new(&self.working_dir)
+            .into_iter()
+            .filter_entry(|e| {
+                e.file_name() != "CMakeFiles"
+                    && std::path::absolute(e.path()).map_or(true, |p| p != wrapper_dir)
+            })
+            .filter_map(Result::ok)
+            .filter(|e| e.file_type().is_file() && is_executable(e.path()))
+            .filter(|e| match wanted_name {
+                Some(name) => e.file_name() == name,
+                None => !e
+                    .path()
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| NON_TARGET_EXTENSIONS.contains(&ext)),
+            })
+            .filter_map(|e| {
+                let modified = e.metadata().ok()?.modified().ok()?;
+                Some((modified, e.path().to_path_buf()))
+            })
+            .collect();
+
+        // Prefer binaries written by this build, newest first
+        candidates.sort();
+        let fresh = candidates.iter().rev().find(|(modified, _)| *modified >= started);
+        match (fresh, wanted_name) {
+            (Some((_, path)), _) => Ok(path.clone()),
+            (None, Some(_)) if !candidates.is_empty() => Ok(candidates.last().unwrap().1.clone()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No binary produced by the build in {:?}", self.working_dir),
+            )),
+        }
+    }
+}
+
+/// Write `cc` and `c++` wrapper scripts that add the coverage and sanitizer flags of
+/// `compiler` to every invocation
+pub fn write_compiler_wrappers(
+    compiler: &CompilerConfig,
+    dir: &Path,
+) -> io::Result<(PathBuf, PathBuf)> {
+    fs::create_dir_all(dir)?;
+    let dir = std::path::absolute(dir)?;
+
+    let mut flags = vec![compiler.optimization_level.clone()];
+    flags.extend(compiler.instrumentation_flags());
+    flags.extend(compiler.extra_flags.iter().cloned());
+
+    let cc = dir.join("fuzz-cc");
+    let cxx = dir.join("fuzz-c++");
+    write_wrapper(&cc, &compiler.compiler_path, &flags)?;
+    write_wrapper(&cxx, &compiler.cxx_driver(), &flags)?;
+    Ok((cc, cxx))
+}
+
+fn write_wrapper(path: &Path, compiler: &str, flags: &[String]) -> io::Result<()> {
+    // Our flags go last so they win over the build system's own -O level
+    let flags: Vec<String> = flags.iter().map(|f| shell_quote(f)).collect();
+    let script = format!(
+        "#!/bin/sh\nexec {} \"$@\" {}\n",
+        shell_quote(compiler),
+        flags.join(" ")
+    );
+    fs::write(path, script)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(())
+}
+
+fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|m| m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "exe")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Language;
+    use crate::sanitizer::Sanitizer;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("-O0"), "'-O0'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn test_wrapped_build_injects_flags() -> Result<(), CompileError> {
+        use std::os::unix::fs::PermissionsExt;
+
+        // Stand-in compiler that logs its arguments and creates an executable output
+        let dir = tempdir()?;
+        let log = dir.path().join("cc.log");
+        let fake_cc = dir.path().join("fake-cc");
+        fs::write(
+            &fake_cc,
+            format!(
+                "#!/bin/sh\necho \"$@\" >> '{}'\nwhile [ $# -gt 0 ]; do\n  if [ \"$1\" = \"-o\" ]; then touch \"$2\"; chmod +x \"$2\"; fi\n  shift\ndone\n",
+                log.display()
+            ),
+        )?;
+        fs::set_permissions(&fake_cc, fs::Permissions::from_mode(0o755))?;
+
+        let project = dir.path().join("project");
+        fs::create_dir_all(&project)?;
+        fs::write(project.join("build.sh"), "$CC $CFLAGS -O2 -o out/prog main.c $LDFLAGS\n")?;
+        fs::create_dir_all(project.join("out"))?;
+
+        let mut compiler = CompilerConfig::new(Language::C)
+            .with_sanitizers(vec![Sanitizer::Address]);
+        compiler.compiler_path = fake_cc.to_string_lossy().into_owned();
+
+        let build = WrappedBuild::new(&project, vec!["sh".to_string(), "build.sh".to_string()]);
+        let binary = build.build(&compiler, &dir.path().join("wrappers"))?;
+        assert_eq!(binary, project.join("out/prog"));
+
+        let logged = fs::read_to_string(&log)?;
+        assert!(logged.contains("-O2 -o out/prog main.c -O0"));
+        // Each flag comes from the wrapper alone, even for builds that use CFLAGS
+        assert_eq!(logged.matches("-fprofile-instr-generate").count(), 1);
+        assert_eq!(logged.matches("-fsanitize=address").count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn test_locate_named_binary() -> io::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir()?;
+        for name in ["tool", "target"] {
+            let path = dir.path().join(name);
+            fs::write(&path, "")?;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+        }
+
+        let build = WrappedBuild::new(dir.path(), vec![]).with_binary(Path::new("bin/target"));
+        let found = build.locate_binary(SystemTime::now(), &dir.path().join("wrappers"))?;
+        assert_eq!(found, dir.path().join("target"));
+        Ok(())
+    }
+}
@@ -0,0 +1,805 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use walkdir::WalkDir;
+
+use super::coverage::{Coverage, CoverageData};
+use super::coverage_report::generate_lcov;
+use super::coverage_visualization::{plot_series, CoverageSeries, MarkerKind, PlotMarker, PlotOptions, XAxis};
+use super::error::CompileError;
+use super::html_report::generate_html_report;
+use super::sanitizer::Sanitizer;
+use super::simple_fuzzer::{Execution, Fuzzer, InputMode, MutationFuzzer, Mutator, ProgramRunner, RandomFuzzer};
+use super::stats_log::StatsLog;
+use super::status::{CampaignStats, StatusScreen};
+use super::toolchain::Toolchain;
+use super::triage::{crash_signature, GENERIC_SIGNATURE};
+use super::CoverageConfig;
+
+/// Which input generator a campaign uses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FuzzerKind {
+    /// Random strings from a character range
+    #[default]
+    Random,
+    /// Mutations of the seeds and of every input that found new coverage
+    Mutation,
+}
+
+impl FromStr for FuzzerKind {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "random" => Ok(FuzzerKind::Random),
+            "mutation" => Ok(FuzzerKind::Mutation),
+            _ => Err(format!("unknown fuzzer `{}` (expected random or mutation)", name)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CampaignConfig {
+    /// Coverage-instrumented target
+    pub binary: PathBuf,
+    pub args: Vec<String>,
+    pub input_mode: InputMode,
+    /// Receives `corpus/`, `crashes/`, `hangs/`, `coverage/` and `fuzzer_stats`
+    pub output_dir: PathBuf,
+    pub fuzzer: FuzzerKind,
+    /// File or directory of initial inputs
+    pub seed_corpus: Option<PathBuf>,
+    /// Stop after this many executions; without any limit the campaign runs until
+    /// interrupted
+    pub max_executions: Option<u64>,
+    pub max_time: Option<Duration>,
+    /// Stop once no input has found new coverage for this long
+    pub max_idle: Option<Duration>,
+    /// Runs taking longer are killed and saved as hangs
+    pub timeout: Duration,
+    /// Sanitizers the target was built with, to set their runtime options
+    pub sanitizers: Vec<Sanitizer>,
+    /// Length and character range of random inputs
+    pub min_length: usize,
+    pub max_length: usize,
+    pub char_start: u32,
+    pub char_range: u32,
+    /// Seed of the fuzzer's RNG; the same seed, target and corpus give the same inputs.
+    /// Picked at random when not set.
+    pub seed: Option<u64>,
+    /// Mutations applied per input by the mutation fuzzer
+    pub min_mutations: usize,
+    pub max_mutations: usize,
+    pub mutators: Vec<Mutator>,
+    /// Corpus directories of other campaigns to import new inputs from
+    pub sync_dirs: Vec<PathBuf>,
+    pub sync_interval: Duration,
+    /// Sources, toolchain and file filter for coverage
+    pub coverage: CoverageConfig,
+}
+
+impl CampaignConfig {
+    pub fn new(binary: &Path, output_dir: &Path) -> Self {
+        let coverage_dir = output_dir.join("coverage");
+        CampaignConfig {
+            binary: binary.to_path_buf(),
+            args: vec![],
+            input_mode: InputMode::default(),
+            output_dir: output_dir.to_path_buf(),
+            fuzzer: FuzzerKind::default(),
+            seed_corpus: None,
+            max_executions: None,
+            max_time: None,
+            max_idle: None,
+            timeout: Duration::from_secs(1),
+            sanitizers: vec![],
+            min_length: 1,
+            max_length: 100,
+            char_start: 32,
+            char_range: 95,
+            seed: None,
+            min_mutations: 1,
+            max_mutations: 5,
+            mutators: Mutator::ALL.to_vec(),
+            sync_dirs: vec![],
+            sync_interval: Duration::from_secs(30),
+            coverage: CoverageConfig {
+                profile_data: coverage_dir.join("coverage.profdata"),
+                output_dir: coverage_dir,
+                binary: Some(binary.to_path_buf()),
+                ..CoverageConfig::default()
+            },
+        }
+    }
+
+    pub fn with_args(mut self, args: Vec<String>) -> Self {
+        self.args.extend(args);
+        self
+    }
+
+    pub fn with_input_mode(mut self, input_mode: InputMode) -> Self {
+        self.input_mode = input_mode;
+        self
+    }
+
+    pub fn with_fuzzer(mut self, fuzzer: FuzzerKind) -> Self {
+        self.fuzzer = fuzzer;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn with_seed_corpus(mut self, seed_corpus: &Path) -> Self {
+        self.seed_corpus = Some(seed_corpus.to_path_buf());
+        self
+    }
+
+    pub fn with_max_executions(mut self, max_executions: u64) -> Self {
+        self.max_executions = Some(max_executions);
+        self
+    }
+
+    pub fn with_max_time(mut self, max_time: Duration) -> Self {
+        self.max_time = Some(max_time);
+        self
+    }
+
+    pub fn with_max_idle(mut self, max_idle: Duration) -> Self {
+        self.max_idle = Some(max_idle);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_sanitizers(mut self, sanitizers: Vec<Sanitizer>) -> Self {
+        self.sanitizers = sanitizers;
+        self
+    }
+
+    /// Periodically run the inputs other campaigns add to these corpus directories,
+    /// keeping the ones that find new coverage here too
+    pub fn with_sync_dirs(mut self, sync_dirs: Vec<PathBuf>, sync_interval: Duration) -> Self {
+        self.sync_dirs = sync_dirs;
+        self.sync_interval = sync_interval;
+        self
+    }
+
+    pub fn with_source_dir(mut self, source_dir: &Path) -> Self {
+        self.coverage.source_dir = source_dir.to_path_buf();
+        self
+    }
+
+    /// Read profiles with these tools, which must match the clang that built the target
+    pub fn with_toolchain(mut self, toolchain: &Toolchain) -> Self {
+        self.coverage.toolchain = toolchain.clone();
+        self
+    }
+
+    pub fn corpus_dir(&self) -> PathBuf {
+        self.output_dir.join("corpus")
+    }
+
+    pub fn crashes_dir(&self) -> PathBuf {
+        self.output_dir.join("crashes")
+    }
+
+    pub fn hangs_dir(&self) -> PathBuf {
+        self.output_dir.join("hangs")
+    }
+
+    pub fn coverage_dir(&self) -> PathBuf {
+        self.coverage.output_dir.clone()
+    }
+
+    /// Runner delivering inputs the configured way, with the sanitizer options and
+    /// the profile location the coverage processing expects
+    pub fn runner(&self) -> ProgramRunner {
+        let profile = self.coverage.output_dir.join("default.profraw");
+        ProgramRunner::new(&self.binary.to_string_lossy())
+            .with_args(self.args.clone())
+            .with_input_mode(self.input_mode)
+            .with_timeout(self.timeout)
+            .with_sanitizers(&self.sanitizers)
+            .with_env("LLVM_PROFILE_FILE", &profile.to_string_lossy())
+    }
+}
+
+/// Why a campaign ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    MaxExecutions,
+    MaxTime,
+    /// No new coverage within `max_idle`
+    NoNewCoverage,
+    /// The flag from `Campaign::stop_flag` was set, e.g. on Ctrl-C
+    Interrupted,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            StopReason::MaxExecutions => "execution limit reached",
+            StopReason::MaxTime => "time limit reached",
+            StopReason::NoNewCoverage => "no new coverage within the idle limit",
+            StopReason::Interrupted => "interrupted",
+        };
+        f.write_str(reason)
+    }
+}
+
+/// What a campaign publishes for a supervisor, e.g. of parallel workers
+#[derive(Debug, Clone, Default)]
+pub struct Progress {
+    pub stats: CampaignStats,
+    /// Coverage so far; only refreshed when it grows
+    pub coverage: CoverageData,
+    /// Bumped every time `coverage` is refreshed
+    pub coverage_version: u64,
+    /// Signatures of the crashes saved so far, so that crashes several campaigns
+    /// found count once
+    pub crash_signatures: HashSet<String>,
+}
+
+enum Generator {
+    Random(RandomFuzzer),
+    Mutation(MutationFuzzer),
+}
+
+impl Generator {
+    fn stage(&self) -> &'static str {
+        match self {
+            Generator::Random(_) => "random",
+            Generator::Mutation(_) => "mutation",
+        }
+    }
+
+    fn next(&self) -> String {
+        match self {
+            Generator::Random(fuzzer) => fuzzer.fuzz(),
+            Generator::Mutation(fuzzer) => fuzzer.fuzz(),
+        }
+    }
+
+    /// Mutations work on characters, so inputs that aren't UTF-8 still run as they are
+    /// but aren't mutated rather than being mutated in a rewritten form
+    fn add(&mut self, input: &[u8]) {
+        if let Generator::Mutation(fuzzer) = self {
+            match std::str::from_utf8(input) {
+                Ok(input) => fuzzer.add_to_population(input.to_string()),
+                Err(_) => eprintln!("warning: not mutating an input that isn't UTF-8 ({} bytes)", input.len()),
+            }
+        }
+    }
+}
+
+/// A coverage-guided fuzzing campaign against one binary: runs the seeds, then
+/// generated inputs until a limit is reached, keeping inputs that find new coverage
+/// and saving crashes and hangs
+pub struct Campaign {
+    config: CampaignConfig,
+    runner: ProgramRunner,
+    coverage: Coverage,
+    generator: Generator,
+    seeds: Vec<Vec<u8>>,
+    seed: u64,
+    stats: CampaignStats,
+    crash_signatures: HashSet<String>,
+    markers: Vec<PlotMarker>,
+    series: [CoverageSeries; 3],
+    status: Option<StatusScreen>,
+    stats_log: StatsLog,
+    stop: Arc<AtomicBool>,
+    progress: Option<Arc<Mutex<Progress>>>,
+    last_sync: Instant,
+    synced: HashSet<PathBuf>,
+}
+
+impl Campaign {
+    pub fn new(config: CampaignConfig) -> io::Result<Self> {
+        config.coverage.file_filter.validate()?;
+        for dir in [config.corpus_dir(), config.crashes_dir(), config.hangs_dir(), config.coverage_dir()] {
+            fs::create_dir_all(dir)?;
+        }
+        let seeds = match &config.seed_corpus {
+            Some(path) => load_inputs(path)?.into_iter().map(|(_, input)| input).collect(),
+            None => vec![],
+        };
+        let seed = config.seed.unwrap_or_else(rand::random);
+        let generator = match config.fuzzer {
+            FuzzerKind::Random => Generator::Random(
+                RandomFuzzer::new(
+                    config.min_length,
+                    config.max_length,
+                    config.char_start,
+                    config.char_range,
+                )
+                .with_seed(seed),
+            ),
+            FuzzerKind::Mutation => Generator::Mutation(
+                MutationFuzzer::new(vec![], config.min_mutations, config.max_mutations)
+                    .with_mutators(config.mutators.clone())
+                    .with_seed(seed),
+            ),
+        };
+
+        let mut coverage = Coverage::new(config.coverage.clone());
+        coverage.reset()?;
+
+        Ok(Campaign {
+            runner: config.runner(),
+            stats_log: StatsLog::new(&config.output_dir),
+            config,
+            coverage,
+            generator,
+            seeds,
+            seed,
+            stats: CampaignStats {
+                seed: Some(seed),
+                ..CampaignStats::default()
+            },
+            crash_signatures: HashSet::new(),
+            markers: vec![],
+            series: [
+                CoverageSeries::new("Lines"),
+                CoverageSeries::new("Branches"),
+                CoverageSeries::new("Functions"),
+            ],
+            status: Some(StatusScreen::new()),
+            stop: Arc::new(AtomicBool::new(false)),
+            progress: None,
+            last_sync: Instant::now(),
+            synced: HashSet::new(),
+        })
+    }
+
+    /// Stop when this flag is set instead of the campaign's own one, e.g. to stop
+    /// several campaigns together
+    pub fn with_stop_flag(mut self, stop: Arc<AtomicBool>) -> Self {
+        self.stop = stop;
+        self
+    }
+
+    /// Publish the stats and coverage here after every run instead of showing the
+    /// status screen
+    pub fn with_progress(mut self, progress: Arc<Mutex<Progress>>) -> Self {
+        self.status = None;
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Setting this flag, e.g. from a Ctrl-C handler, ends the campaign after the
+    /// current run; the reports are still written
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.stop)
+    }
+
+    pub fn config(&self) -> &CampaignConfig {
+        &self.config
+    }
+
+    /// Seed of the fuzzer's RNG, to rerun the campaign with
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn stats(&self) -> &CampaignStats {
+        &self.stats
+    }
+
+    pub fn coverage_data(&self) -> &CoverageData {
+        self.coverage.get_coverage_data()
+    }
+
+    /// Run the seeds, then generated inputs until a limit is reached or the campaign is
+    /// stopped, and write the coverage reports
+    pub fn run(&mut self) -> io::Result<StopReason> {
+        match self.run_inputs() {
+            Ok(reason) => {
+                self.finish()?;
+                Ok(reason)
+            }
+            Err(e) => {
+                // Keep what was found before the error; the error is what gets reported
+                let _ = self.finish();
+                Err(e)
+            }
+        }
+    }
+
+    fn run_inputs(&mut self) -> io::Result<StopReason> {
+        self.stats.stage = "seeds".to_string();
+        for seed in std::mem::take(&mut self.seeds) {
+            if self.stop.load(Ordering::Relaxed) {
+                break;
+            }
+            self.generator.add(&seed);
+            self.run_one(&seed)?;
+        }
+
+        self.stats.stage = self.generator.stage().to_string();
+        let reason = loop {
+            if let Some(reason) = self.stop_reason() {
+                break reason;
+            }
+            if !self.config.sync_dirs.is_empty()
+                && self.last_sync.elapsed() >= self.config.sync_interval
+            {
+                self.sync()?;
+            }
+            let input = self.generator.next();
+            self.run_one(&input)?;
+        };
+        Ok(reason)
+    }
+
+    fn stop_reason(&self) -> Option<StopReason> {
+        let since_new_coverage = self.stats.last_new_path.unwrap_or(self.stats.start).elapsed();
+        if self.stop.load(Ordering::Relaxed) {
+            Some(StopReason::Interrupted)
+        } else if self.config.max_executions.is_some_and(|max| self.stats.executions >= max) {
+            Some(StopReason::MaxExecutions)
+        } else if self.config.max_time.is_some_and(|max| self.stats.elapsed() >= max) {
+            Some(StopReason::MaxTime)
+        } else if self.config.max_idle.is_some_and(|max| since_new_coverage >= max) {
+            Some(StopReason::NoNewCoverage)
+        } else {
+            None
+        }
+    }
+
+    /// Run one input, recording its coverage and saving it if it's interesting.
+    ///
+    /// Every run overwrites the target's raw profile, so its coverage is processed
+    /// right away: an `llvm-profdata merge`, a full `llvm-cov export` and parsing the
+    /// export. That costs far more than the run itself for most targets and limits a
+    /// campaign to a few executions per second; more workers are the way to go faster.
+    pub fn run_one<I: AsRef<[u8]> + ?Sized>(&mut self, input: &I) -> io::Result<Execution> {
+        let input = input.as_ref();
+        let execution = self.runner.execute(input)?;
+        // Ctrl-C reaches the target too, so don't count the run it killed as a crash
+        if self.stop.load(Ordering::Relaxed) {
+            return Ok(execution);
+        }
+        self.stats.executions += 1;
+        let id = self.stats.executions;
+
+        if execution.timed_out {
+            self.stats.hangs += 1;
+            fs::write(self.config.hangs_dir().join(format!("hang_{:06}", id)), input)?;
+        } else if execution.crashed() {
+            let mut signature = crash_signature(&String::from_utf8_lossy(&execution.stderr));
+            // Without a sanitizer report all crashes look alike, so only drop exact
+            // repeats of the same input
+            if signature == GENERIC_SIGNATURE {
+                let mut hasher = DefaultHasher::new();
+                input.hash(&mut hasher);
+                signature = format!("{} ({}) {:016x}", signature, execution.status, hasher.finish());
+            }
+            // Keep one input per distinct crash
+            if self.crash_signatures.insert(signature) {
+                self.stats.unique_crashes += 1;
+                fs::write(self.config.crashes_dir().join(format!("crash_{:06}", id)), input)?;
+                fs::write(self.config.crashes_dir().join(format!("crash_{:06}.stderr", id)), &execution.stderr)?;
+            }
+            self.markers.push(self.marker(MarkerKind::Crash));
+        }
+
+        let found_new = self.update_coverage(input)?;
+        if let Some(status) = &mut self.status {
+            status.update(&self.stats)?;
+        }
+        if let Some(progress) = &self.progress {
+            let mut progress = progress.lock().unwrap_or_else(|e| e.into_inner());
+            progress.stats = self.stats.clone();
+            if progress.crash_signatures.len() != self.crash_signatures.len() {
+                progress.crash_signatures.clone_from(&self.crash_signatures);
+            }
+            if found_new {
+                progress.coverage = self.coverage.get_coverage_data().clone();
+                progress.coverage_version += 1;
+            }
+        }
+        self.stats_log.record(&self.stats)?;
+        Ok(execution)
+    }
+
+    /// Run the inputs added to the sync directories since the last sync
+    fn sync(&mut self) -> io::Result<()> {
+        self.last_sync = Instant::now();
+        let stage = std::mem::replace(&mut self.stats.stage, "sync".to_string());
+        for dir in self.config.sync_dirs.clone() {
+            let Ok(entries) = fs::read_dir(&dir) else {
+                // The other campaign may not have started yet
+                continue;
+            };
+            let mut new_files: Vec<PathBuf> = entries
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    let hidden = path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'));
+                    path.is_file() && !hidden && !self.synced.contains(path)
+                })
+                .collect();
+            new_files.sort();
+            for path in new_files {
+                if self.stop.load(Ordering::Relaxed) {
+                    break;
+                }
+                let input = fs::read(&path)?;
+                self.synced.insert(path);
+                self.run_one(&input)?;
+            }
+        }
+        self.stats.stage = stage;
+        Ok(())
+    }
+
+    /// Returns whether the input found new coverage
+    fn update_coverage(&mut self, input: &[u8]) -> io::Result<bool> {
+        match self.coverage.process_coverage() {
+            Ok(()) => {}
+            // Nothing has written a profile yet, e.g. every run so far crashed
+            Err(CompileError::Io(e)) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(io::Error::other(e)),
+        }
+
+        let summary = self.coverage.get_coverage_data().summary();
+        let previous = &self.stats.coverage;
+        let found_new = summary.covered_lines > previous.covered_lines
+            || summary.covered_branches > previous.covered_branches
+            || summary.covered_regions > previous.covered_regions;
+        if found_new {
+            self.stats.corpus_size += 1;
+            self.stats.last_new_path = Some(Instant::now());
+            // Written under a hidden name first so that syncing campaigns never see a
+            // partial file
+            let name = format!("id_{:06}", self.stats.executions);
+            let partial = self.config.corpus_dir().join(format!(".{}", name));
+            fs::write(&partial, input)?;
+            fs::rename(partial, self.config.corpus_dir().join(name))?;
+            self.generator.add(input);
+            self.markers.push(self.marker(MarkerKind::NewSeed));
+        }
+
+        let elapsed = self.stats.elapsed();
+        let executions = self.stats.executions;
+        let values = [summary.line_coverage(), summary.branch_coverage(), summary.function_coverage()];
+        for (series, value) in self.series.iter_mut().zip(values) {
+            series.push(executions, elapsed, value);
+        }
+        self.stats.coverage = summary;
+        Ok(found_new)
+    }
+
+    fn marker(&self, kind: MarkerKind) -> PlotMarker {
+        PlotMarker {
+            kind,
+            executions: self.stats.executions,
+            elapsed: self.stats.elapsed(),
+        }
+    }
+
+    /// Coverage of a single input on its own, forgetting what earlier runs covered
+    pub fn coverage_of<I: AsRef<[u8]> + ?Sized>(&mut self, input: &I) -> io::Result<CoverageData> {
+        self.coverage.reset()?;
+        self.runner.execute(input)?;
+        match self.coverage.process_coverage() {
+            Ok(()) => Ok(self.coverage.get_coverage_data().clone()),
+            Err(CompileError::Io(e)) if e.kind() == io::ErrorKind::NotFound => {
+                Ok(CoverageData::default())
+            }
+            Err(e) => Err(io::Error::other(e)),
+        }
+    }
+
+    /// Show the final stats and write the LCOV, HTML and plot reports
+    pub fn finish(&mut self) -> io::Result<()> {
+        if let Some(status) = &mut self.status {
+            status.draw(&self.stats)?;
+        }
+        self.stats_log.write(&self.stats)?;
+
+        let coverage_dir = self.config.coverage_dir();
+        let data = self.coverage.get_coverage_data();
+        generate_lcov(data, &coverage_dir.join("coverage.lcov"))?;
+        let history: Vec<f64> = self.series[0].points.iter().map(|p| p.coverage).collect();
+        generate_html_report(data, &history, &coverage_dir.join("html"))?;
+
+        let options = PlotOptions::default()
+            .with_x_axis(XAxis::ElapsedTime)
+            .with_markers(self.markers.clone());
+        plot_series(&self.series, &options, &coverage_dir.join("coverage_over_time.svg"))
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        Ok(())
+    }
+}
+
+/// Read a single input file, or every file under a directory in path order
+pub fn load_inputs(path: &Path) -> io::Result<Vec<(PathBuf, Vec<u8>)>> {
+    let mut files: Vec<PathBuf> = if path.is_dir() {
+        WalkDir::new(path)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.into_path())
+            .collect()
+    } else {
+        vec![path.to_path_buf()]
+    };
+    files.sort();
+
+    files
+        .into_iter()
+        .map(|file| {
+            let bytes = fs::read(&file)?;
+            Ok((file, bytes))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_layout() {
+        let toolchain = Toolchain {
+            llvm_cov: PathBuf::from("llvm-cov-17"),
+            ..Toolchain::default()
+        };
+        let config = CampaignConfig::new(Path::new("bin/target"), Path::new("out"))
+            .with_fuzzer(FuzzerKind::Mutation)
+            .with_max_executions(10)
+            .with_toolchain(&toolchain);
+        assert_eq!(config.coverage.toolchain, toolchain);
+        assert_eq!(config.crashes_dir(), PathBuf::from("out/crashes"));
+        assert_eq!(config.coverage.output_dir, PathBuf::from("out/coverage"));
+        assert_eq!(config.coverage.binary, Some(PathBuf::from("bin/target")));
+        assert_eq!("mutation".parse(), Ok(FuzzerKind::Mutation));
+        assert!("grammar".parse::<FuzzerKind>().is_err());
+    }
+
+    #[test]
+    fn test_generator_skips_non_utf8() {
+        let mut generator = Generator::Mutation(MutationFuzzer::new(vec![], 1, 5));
+        generator.add(b"ok");
+        generator.add(&[0xff, b'x']);
+        let Generator::Mutation(fuzzer) = generator else { unreachable!() };
+        assert_eq!(fuzzer.population(), ["ok"]);
+    }
+
+    #[test]
+    fn test_invalid_glob_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = CampaignConfig::new(Path::new("true"), dir.path());
+        config.coverage.file_filter.exclude = vec!["[foo".to_string()];
+        let err = Campaign::new(config).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_load_inputs() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::create_dir(dir.path().join("nested"))?;
+        fs::write(dir.path().join("b"), "second")?;
+        fs::write(dir.path().join("a"), "first")?;
+        fs::write(dir.path().join("nested/c"), [0xff, b'x'])?;
+
+        let inputs = load_inputs(dir.path())?;
+        let contents: Vec<&[u8]> = inputs.iter().map(|(_, input)| input.as_slice()).collect();
+        assert_eq!(contents, vec![&b"first"[..], b"second", &[0xff, b'x']]);
+        assert_eq!(load_inputs(&dir.path().join("a"))?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn test_campaign_stops() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join("out");
+
+        // `true` never writes coverage, so the campaign is idle from the start
+        let config = CampaignConfig::new(Path::new("true"), &output).with_max_idle(Duration::ZERO);
+        let mut campaign = Campaign::new(config)?;
+        assert_eq!(campaign.run()?, StopReason::NoNewCoverage);
+        assert!(output.join("coverage/coverage.lcov").exists());
+        assert!(output.join("coverage/coverage_over_time.svg").exists());
+        assert!(output.join(crate::STATS_FILE).exists());
+
+        let mut campaign = Campaign::new(CampaignConfig::new(Path::new("true"), &output))?;
+        campaign.stop_flag().store(true, Ordering::Relaxed);
+        assert_eq!(campaign.run()?, StopReason::Interrupted);
+        assert_eq!(campaign.stats().executions, 0);
+
+        // Reports are still written when a run fails
+        let failing = dir.path().join("failing");
+        let mut campaign = Campaign::new(CampaignConfig::new(&dir.path().join("missing"), &failing))?;
+        assert!(campaign.run().is_err());
+        assert!(failing.join("coverage/coverage.lcov").exists());
+        assert!(failing.join(crate::STATS_FILE).exists());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn test_campaign_syncs() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let script = dir.path().join("target.sh");
+        fs::write(&script, "#!/bin/sh\ncase \"$1\" in *!*) kill -SEGV $$;; esac\n")?;
+        std::process::Command::new("chmod").arg("+x").arg(&script).status()?;
+        // Another campaign's corpus holding the only crashing input
+        let other = dir.path().join("other");
+        fs::create_dir(&other)?;
+        fs::write(other.join("id_000001"), "found elsewhere!")?;
+        fs::write(other.join(".id_000002"), "still being written!")?;
+
+        let mut config = CampaignConfig::new(&script, &dir.path().join("out"))
+            .with_sync_dirs(vec![other], Duration::ZERO)
+            .with_max_executions(5);
+        // Random inputs of lowercase letters never crash on their own
+        config.char_start = 'a' as u32;
+        config.char_range = 26;
+        let mut campaign = Campaign::new(config)?;
+        campaign.run()?;
+        assert_eq!(campaign.stats().unique_crashes, 1);
+        assert_eq!(campaign.stats().executions, 5);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn test_campaign_saves_crashes_and_hangs() -> io::Result<()> {
+        // A "target" that crashes on inputs containing `!` and hangs on `~`; it writes
+        // no coverage profile, so coverage stays empty
+        let dir = tempfile::tempdir()?;
+        let script = dir.path().join("target.sh");
+        fs::write(
+            &script,
+            "#!/bin/sh\ncase \"$1\" in *!*) kill -SEGV $$;; *~*) sleep 5;; esac\n",
+        )?;
+        std::process::Command::new("chmod").arg("+x").arg(&script).status()?;
+
+        let seeds = dir.path().join("seeds");
+        fs::create_dir(&seeds)?;
+        fs::write(seeds.join("1"), "ok")?;
+        fs::write(seeds.join("2"), "boom!")?;
+        fs::write(seeds.join("3"), "bang!")?;
+        fs::write(seeds.join("4"), "~")?;
+        fs::write(seeds.join("5"), "boom!")?;
+
+        let output = dir.path().join("out");
+        let config = CampaignConfig::new(&script, &output)
+            .with_seed_corpus(&seeds)
+            .with_timeout(Duration::from_millis(100))
+            .with_max_executions(5);
+        let mut campaign = Campaign::new(config)?;
+        for (_, seed) in load_inputs(&seeds)? {
+            campaign.run_one(&seed)?;
+        }
+
+        let stats = campaign.stats();
+        assert_eq!(stats.executions, 5);
+        // No sanitizer report, so only the repeated input counts as the same crash
+        assert_eq!(stats.unique_crashes, 2);
+        assert_eq!(stats.hangs, 1);
+        assert!(output.join("crashes/crash_000002").exists());
+        assert!(output.join("crashes/crash_000003").exists());
+        assert!(output.join("hangs/hang_000004").exists());
+        assert!(!output.join("crashes/crash_000005").exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,440 @@
+use std::fmt::Display;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use glob::Pattern;
+use serde::Deserialize;
+
+use super::campaign::{CampaignConfig, FuzzerKind};
+use super::compiler::{detect_language, CompilerConfig, Language};
+use super::sanitizer::{validate_sanitizers, Sanitizer};
+use super::simple_fuzzer::Mutator;
+use super::toolchain::Toolchain;
+
+/// A campaign described by a TOML file, e.g. `campaign.toml` checked into the
+/// target's repository:
+///
+/// ```toml
+/// [target]
+/// sources = ["src/cgi_decode.c"]     # or: binary = "bin/cgi_decode"
+/// flags = ["-DTESTING"]
+/// sanitizers = ["address"]
+///
+/// [runner]
+/// input_mode = "file"
+/// args = ["--decode", "@@"]
+/// timeout_ms = 500
+///
+/// [fuzzer]
+/// kind = "mutation"
+/// seed = 1234                        # fixed RNG seed, to replay the campaign
+/// seeds = "seeds"
+/// mutators = ["insert", "flip"]
+/// max_time_secs = 600
+/// max_idle_secs = 120
+/// workers = 8
+///
+/// [coverage]
+/// source_dir = "src"
+/// exclude = ["**/test_*.c"]
+///
+/// [output]
+/// dir = "fuzz_out"
+/// ```
+///
+/// Relative paths are resolved against the directory containing the file.
+#[derive(Debug, Clone)]
+pub struct CampaignFile {
+    /// Sources to build `config.binary` from first; empty when the file names a binary
+    pub sources: Vec<PathBuf>,
+    pub config: CampaignConfig,
+    /// Number of parallel workers
+    pub workers: usize,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawFile {
+    target: RawTarget,
+    #[serde(default)]
+    runner: RawRunner,
+    #[serde(default)]
+    fuzzer: RawFuzzer,
+    #[serde(default)]
+    coverage: RawCoverage,
+    #[serde(default)]
+    output: RawOutput,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawTarget {
+    binary: Option<PathBuf>,
+    #[serde(default)]
+    sources: Vec<PathBuf>,
+    language: Option<String>,
+    compiler: Option<String>,
+    optimization: Option<String>,
+    #[serde(default)]
+    flags: Vec<String>,
+    #[serde(default)]
+    sanitizers: Vec<String>,
+    #[serde(default)]
+    mcdc: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRunner {
+    input_mode: Option<String>,
+    #[serde(default)]
+    args: Vec<String>,
+    timeout_ms: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawFuzzer {
+    kind: Option<String>,
+    seeds: Option<PathBuf>,
+    max_executions: Option<u64>,
+    max_time_secs: Option<u64>,
+    /// Stop once no input has found new coverage for this long
+    max_idle_secs: Option<u64>,
+    seed: Option<u64>,
+    workers: Option<usize>,
+    sync_interval_secs: Option<u64>,
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+    char_start: Option<u32>,
+    char_range: Option<u32>,
+    min_mutations: Option<usize>,
+    max_mutations: Option<usize>,
+    mutators: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawCoverage {
+    source_dir: Option<PathBuf>,
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
+    exclude: Vec<String>,
+    exclude_system_headers: Option<bool>,
+    /// `llvm-config` of the LLVM release whose clang and coverage tools to use
+    llvm_config: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawOutput {
+    dir: Option<PathBuf>,
+}
+
+/// Read and validate a campaign file
+pub fn load_campaign_file(path: &Path) -> io::Result<CampaignFile> {
+    let text = fs::read_to_string(path)?;
+    // `./` keeps a binary next to the file from being looked up on PATH
+    let base_dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    parse_campaign_file(&text, base_dir)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+}
+
+/// Parse a campaign file, resolving relative paths against `base_dir`. Errors name the
+/// offending key.
+pub fn parse_campaign_file(text: &str, base_dir: &Path) -> io::Result<CampaignFile> {
+    let raw: RawFile = toml::from_str(text)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    let resolve = |path: &Path| base_dir.join(path);
+
+    let target = &raw.target;
+    let sources: Vec<PathBuf> = target
+        .sources
+        .iter()
+        .map(|source| resolve(source))
+        .collect();
+    let output_dir = resolve(raw.output.dir.as_deref().unwrap_or(Path::new("fuzz_out")));
+    let binary = match (&target.binary, sources.is_empty()) {
+        (Some(binary), true) => resolve(binary),
+        (None, false) => output_dir.join("bin/target"),
+        (Some(_), false) => {
+            return Err(invalid(
+                "target.binary",
+                "cannot be combined with `target.sources`",
+            ))
+        }
+        (None, true) => return Err(invalid("target", "needs either `binary` or `sources`")),
+    };
+
+    let language = match &target.language {
+        Some(name) => match name.as_str() {
+            "c" => Language::C,
+            "c++" | "cpp" => Language::Cpp,
+            _ => {
+                return Err(invalid(
+                    "target.language",
+                    format!("unknown language `{}` (expected c or c++)", name),
+                ))
+            }
+        },
+        None => sources
+            .iter()
+            .filter_map(|source| detect_language(source))
+            .max_by_key(|language| *language == Language::Cpp)
+            .unwrap_or(Language::C),
+    };
+    let sanitizers: Vec<Sanitizer> = parse_list("target.sanitizers", &target.sanitizers)?;
+    validate_sanitizers(&sanitizers).map_err(|e| invalid("target.sanitizers", e))?;
+
+    let toolchain = match &raw.coverage.llvm_config {
+        Some(llvm_config) => Toolchain::from_llvm_config(&resolve(llvm_config))
+            .map_err(|e| invalid("coverage.llvm_config", e))?,
+        None => Toolchain::discover().unwrap_or_default(),
+    };
+    let mut compiler = CompilerConfig::new(language)
+        .with_toolchain(&toolchain)
+        .with_flags(target.flags.clone())
+        .with_sanitizers(sanitizers.clone());
+    if let Some(path) = &target.compiler {
+        compiler.compiler_path = path.clone();
+    }
+    if let Some(level) = &target.optimization {
+        if !level.starts_with("-O") {
+            return Err(invalid(
+                "target.optimization",
+                format!("`{}` is not an -O flag", level),
+            ));
+        }
+        compiler = compiler.with_optimization(level);
+    }
+    if target.mcdc {
+        compiler = compiler.with_mcdc();
+    }
+
+    let mut config = CampaignConfig::new(&binary, &output_dir)
+        .with_args(raw.runner.args.clone())
+        .with_sanitizers(sanitizers);
+    if let Some(mode) = &raw.runner.input_mode {
+        config = config.with_input_mode(parse_value("runner.input_mode", mode)?);
+    }
+    if let Some(timeout_ms) = raw.runner.timeout_ms {
+        if timeout_ms == 0 {
+            return Err(invalid("runner.timeout_ms", "must be greater than 0"));
+        }
+        config = config.with_timeout(Duration::from_millis(timeout_ms));
+    }
+
+    let fuzzer = &raw.fuzzer;
+    if let Some(kind) = &fuzzer.kind {
+        config = config.with_fuzzer(parse_value::<FuzzerKind>("fuzzer.kind", kind)?);
+    }
+    if let Some(seeds) = &fuzzer.seeds {
+        config = config.with_seed_corpus(&resolve(seeds));
+    }
+    if let Some(max_executions) = fuzzer.max_executions {
+        config = config.with_max_executions(max_executions);
+    }
+    if let Some(max_time_secs) = fuzzer.max_time_secs {
+        config = config.with_max_time(Duration::from_secs(max_time_secs));
+    }
+    if let Some(max_idle_secs) = fuzzer.max_idle_secs {
+        config = config.with_max_idle(Duration::from_secs(max_idle_secs));
+    }
+    config.seed = fuzzer.seed;
+    let workers = fuzzer.workers.unwrap_or(1);
+    if workers == 0 {
+        return Err(invalid("fuzzer.workers", "must be at least 1"));
+    }
+    if let Some(sync_interval_secs) = fuzzer.sync_interval_secs {
+        config.sync_interval = Duration::from_secs(sync_interval_secs);
+    }
+    config.min_length = fuzzer.min_length.unwrap_or(config.min_length);
+    config.max_length = fuzzer.max_length.unwrap_or(config.max_length);
+    if config.min_length > config.max_length {
+        return Err(invalid(
+            "fuzzer.max_length",
+            "is less than `fuzzer.min_length`",
+        ));
+    }
+    config.char_start = fuzzer.char_start.unwrap_or(config.char_start);
+    config.char_range = fuzzer.char_range.unwrap_or(config.char_range);
+    if config.char_range == 0 {
+        return Err(invalid("fuzzer.char_range", "must be greater than 0"));
+    }
+    config.min_mutations = fuzzer.min_mutations.unwrap_or(config.min_mutations);
+    config.max_mutations = fuzzer.max_mutations.unwrap_or(config.max_mutations);
+    if config.min_mutations > config.max_mutations {
+        return Err(invalid(
+            "fuzzer.max_mutations",
+            "is less than `fuzzer.min_mutations`",
+        ));
+    }
+    if let Some(mutators) = &fuzzer.mutators {
+        if mutators.is_empty() {
+            return Err(invalid("fuzzer.mutators", "must name at least one mutator"));
+        }
+        config.mutators = parse_list::<Mutator>("fuzzer.mutators", mutators)?;
+    }
+
+    let coverage = &raw.coverage;
+    for (key, globs) in [
+        ("coverage.include", &coverage.include),
+        ("coverage.exclude", &coverage.exclude),
+    ] {
+        for (index, glob) in globs.iter().enumerate() {
+            Pattern::new(glob).map_err(|e| invalid(&format!("{}[{}]", key, index), e))?;
+        }
+    }
+    let source_dir = match (&coverage.source_dir, sources.first()) {
+        (Some(dir), _) => resolve(dir),
+        (None, Some(source)) => source
+            .parent()
+            .map_or(base_dir.to_path_buf(), Path::to_path_buf),
+        (None, None) => base_dir.to_path_buf(),
+    };
+    config = config.with_source_dir(&source_dir);
+    config.coverage.file_filter.include = coverage.include.clone();
+    config.coverage.file_filter.exclude = coverage.exclude.clone();
+    if let Some(exclude_system_headers) = coverage.exclude_system_headers {
+        config.coverage.file_filter.exclude_system_headers = exclude_system_headers;
+    }
+    config.coverage.compiler = compiler;
+    config.coverage.toolchain = toolchain;
+
+    Ok(CampaignFile {
+        sources,
+        config,
+        workers,
+    })
+}
+
+fn invalid(key: &str, message: impl Display) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid `{}`: {}", key, message),
+    )
+}
+
+fn parse_value<T: FromStr<Err = String>>(key: &str, value: &str) -> io::Result<T> {
+    value.parse().map_err(|e| invalid(key, e))
+}
+
+/// Parse every element of a list, naming the element's index on error
+fn parse_list<T: FromStr<Err = String>>(key: &str, values: &[String]) -> io::Result<Vec<T>> {
+    values
+        .iter()
+        .enumerate()
+        .map(|(index, value)| parse_value(&format!("{}[{}]", key, index), value))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InputMode;
+
+    #[test]
+    fn test_parse_campaign_file() -> io::Result<()> {
+        let file = parse_campaign_file(
+            r#"
+            [target]
+            sources = ["src/decode.c"]
+            flags = ["-DTESTING"]
+            sanitizers = ["address", "ubsan"]
+
+            [runner]
+            input_mode = "file"
+            args = ["--decode", "@@"]
+            timeout_ms = 250
+
+            [fuzzer]
+            kind = "mutation"
+            seed = 1234
+            seeds = "seeds"
+            mutators = ["flip"]
+            max_time_secs = 60
+            max_idle_secs = 30
+            workers = 4
+
+            [coverage]
+            exclude = ["**/test_*.c"]
+            "#,
+            Path::new("/work"),
+        )?;
+
+        let config = &file.config;
+        assert_eq!(file.sources, vec![PathBuf::from("/work/src/decode.c")]);
+        assert_eq!(config.binary, PathBuf::from("/work/fuzz_out/bin/target"));
+        assert_eq!(config.coverage.source_dir, PathBuf::from("/work/src"));
+        assert_eq!(
+            config.sanitizers,
+            vec![Sanitizer::Address, Sanitizer::Undefined]
+        );
+        assert_eq!(config.coverage.compiler.extra_flags, vec!["-DTESTING"]);
+        assert_eq!(config.input_mode, InputMode::File);
+        assert_eq!(config.args, vec!["--decode", "@@"]);
+        assert_eq!(config.timeout, Duration::from_millis(250));
+        assert_eq!(config.fuzzer, FuzzerKind::Mutation);
+        assert_eq!(config.seed_corpus, Some(PathBuf::from("/work/seeds")));
+        assert_eq!(config.mutators, vec![Mutator::Flip]);
+        assert_eq!(config.max_time, Some(Duration::from_secs(60)));
+        assert_eq!(config.max_idle, Some(Duration::from_secs(30)));
+        assert_eq!(file.workers, 4);
+        assert_eq!(config.seed, Some(1234));
+        assert_eq!(config.max_executions, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_example_campaign_file() -> io::Result<()> {
+        let file = load_campaign_file(Path::new("examples/cgi_decode/campaign.toml"))?;
+        assert_eq!(file.config.max_executions, Some(1000));
+        assert_eq!(file.config.sanitizers, vec![Sanitizer::Address]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors_name_the_key() {
+        let error = |text: &str| {
+            parse_campaign_file(text, Path::new(""))
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert!(
+            error("[target]\nbinary = \"a\"\n[runner]\ninput_mode = \"socket\"")
+                .contains("`runner.input_mode`")
+        );
+        assert!(
+            error("[target]\nbinary = \"a\"\nsanitizers = [\"address\", \"bogus\"]")
+                .contains("`target.sanitizers[1]`")
+        );
+        assert!(
+            error("[target]\nbinary = \"a\"\nsanitizers = [\"address\", \"memory\"]")
+                .contains("`target.sanitizers`")
+        );
+        assert!(
+            error("[target]\nbinary = \"a\"\n[fuzzer]\nkind = \"grammar\"")
+                .contains("`fuzzer.kind`")
+        );
+        assert!(
+            error("[target]\nbinary = \"a\"\n[fuzzer]\nmin_length = 5\nmax_length = 2")
+                .contains("`fuzzer.max_length`")
+        );
+        assert!(
+            error("[target]\nbinary = \"a\"\n[coverage]\ninclude = [\"[\"]")
+                .contains("`coverage.include[0]`")
+        );
+        assert!(error("[target]\n").contains("`target`"));
+        // Unknown keys and wrong types are reported by the TOML parser with their position
+        assert!(error("[target]\nbinary = \"a\"\n[runner]\ntimeout = 5").contains("timeout"));
+        assert!(error("[target]\nbinary = \"a\"\n[runner]\ntimeout_ms = \"5\"").contains("line 4"));
+    }
+}
@@ -1,6 +1,9 @@
-use std::path::Path;
-use std::process::Command;
-use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use super::error::{parse_diagnostics, CompileError};
+use super::sanitizer::{self, Sanitizer};
+use super::toolchain::Toolchain;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Language {
@@ -31,6 +34,16 @@ pub struct CompilerConfig {
     pub compiler_path: String,
     pub optimization_level: String,
     pub extra_flags: Vec<String>,
+    pub sanitizers: Vec<Sanitizer>,
+    /// Record MC/DC (modified condition/decision coverage); needs clang 18 or newer
+    pub mcdc: bool,
+}
+
+/// A binary built with one particular set of sanitizers
+#[derive(Debug, Clone)]
+pub struct SanitizerBuild {
+    pub sanitizers: Vec<Sanitizer>,
+    pub binary: PathBuf,
 }
 
 impl Default for CompilerConfig {
@@ -40,6 +53,8 @@ impl Default for CompilerConfig {
             compiler_path: "clang".to_string(),
             optimization_level: "-O0".to_string(),
             extra_flags: vec![],
+            sanitizers: vec![],
+            mcdc: false,
         }
     }
 }
@@ -58,34 +73,128 @@ impl CompilerConfig {
         &self,
         source_file: &Path,
         output_file: &Path,
-    ) -> io::Result<()> {
-        let status = Command::new(&self.compiler_path)
+    ) -> Result<(), CompileError> {
+        self.validate()?;
+
+        let output = Command::new(&self.compiler_path)
             .arg(source_file)
             .arg("-o")
             .arg(output_file)
             .arg(self.optimization_level.as_str())
-            .arg("--coverage")  // Enable gcov/llvm coverage
-            .arg("-fprofile-instr-generate") // LLVM coverage instrumentation
-            .arg("-fcoverage-mapping")       // Enable coverage mapping
+            .args(self.instrumentation_flags())
             .args(&self.extra_flags)
-            .status()?;
+            .output()
+            .map_err(|e| CompileError::from_spawn(&self.compiler_path, e))?;
 
-        if !status.success() {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                "Compilation failed",
-            ));
+        if !output.status.success() {
+            return Err(compilation_failed(source_file, &output));
         }
 
         Ok(())
     }
 
+    /// Check the configuration before invoking the compiler
+    pub fn validate(&self) -> Result<(), CompileError> {
+        sanitizer::validate_sanitizers(&self.sanitizers)
+    }
+
+    /// Coverage and sanitizer flags, needed both when compiling and when linking
+    pub fn instrumentation_flags(&self) -> Vec<String> {
+        let mut flags = vec![
+            "--coverage".to_string(),                // Enable gcov/llvm coverage
+            "-fprofile-instr-generate".to_string(),  // LLVM coverage instrumentation
+            "-fcoverage-mapping".to_string(),        // Enable coverage mapping
+        ];
+        if self.mcdc {
+            flags.push("-fcoverage-mcdc".to_string());
+        }
+        flags.extend(sanitizer::sanitizer_flags(&self.sanitizers));
+        flags
+    }
+
+    /// Compiler driver to use when linking C++ objects, e.g. `clang-17` -> `clang++-17`
+    pub fn cxx_driver(&self) -> String {
+        if self.language == Language::Cpp || self.compiler_path.contains("++") {
+            return self.compiler_path.clone();
+        }
+        match self.compiler_path.rfind("clang") {
+            Some(idx) => {
+                let (head, tail) = self.compiler_path.split_at(idx + "clang".len());
+                format!("{}++{}", head, tail)
+            }
+            None => self.compiler_path.clone(),
+        }
+    }
+
+    /// Compile one binary per sanitizer set, named `<stem>_<variant>` in `output_dir`
+    pub fn compile_sanitizer_variants(
+        &self,
+        source_file: &Path,
+        output_dir: &Path,
+        variants: &[Vec<Sanitizer>],
+    ) -> Result<Vec<SanitizerBuild>, CompileError> {
+        for variant in variants {
+            sanitizer::validate_sanitizers(variant)?;
+        }
+        std::fs::create_dir_all(output_dir)?;
+
+        let stem = source_file
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("target");
+
+        let mut builds = Vec::new();
+        for variant in variants {
+            let binary = output_dir.join(format!(
+                "{}_{}",
+                stem,
+                sanitizer::variant_suffix(variant)
+            ));
+            let config = CompilerConfig {
+                sanitizers: variant.clone(),
+                ..self.clone()
+            };
+            config.compile_with_coverage(source_file, &binary)?;
+            builds.push(SanitizerBuild {
+                sanitizers: variant.clone(),
+                binary,
+            });
+        }
+        Ok(builds)
+    }
+
     /// Add compiler flags
     pub fn with_flags(mut self, flags: Vec<String>) -> Self {
         self.extra_flags.extend(flags);
         self
     }
 
+    /// Use the clang driver of a discovered toolchain
+    pub fn with_toolchain(mut self, toolchain: &Toolchain) -> Self {
+        let driver = match self.language {
+            Language::C => &toolchain.clang,
+            Language::Cpp => &toolchain.clangxx,
+        };
+        self.compiler_path = driver.to_string_lossy().into_owned();
+        self
+    }
+
+    /// Enable MC/DC coverage instrumentation
+    pub fn with_mcdc(mut self) -> Self {
+        self.mcdc = true;
+        self
+    }
+
+    /// Enable sanitizers
+    pub fn with_sanitizers(mut self, sanitizers: Vec<Sanitizer>) -> Self {
+        for sanitizer in sanitizers {
+            if !self.sanitizers.contains(&sanitizer) {
+                self.sanitizers.push(sanitizer);
+            }
+        }
+        self
+    }
+
     /// Set optimization level
     pub fn with_optimization(mut self, level: &str) -> Self {
         self.optimization_level = level.to_string();
@@ -93,6 +202,17 @@ impl CompilerConfig {
     }
 }
 
+/// Build the error for a compiler invocation that exited unsuccessfully
+pub(crate) fn compilation_failed(input: &Path, output: &Output) -> CompileError {
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    CompileError::CompilationFailed {
+        input: input.to_path_buf(),
+        status: output.status,
+        diagnostics: parse_diagnostics(&stderr),
+        stderr,
+    }
+}
+
 pub fn detect_language(source_file: &Path) -> Option<Language> {
     source_file
         .extension()
@@ -104,7 +224,7 @@ pub fn detect_language(source_file: &Path) -> Option<Language> {
 mod tests {
     use super::*;
     use std::fs::File;
-    use std::io::Write;
+    use std::io::{self, Write};
     use tempfile::tempdir;
 
     #[test]
@@ -134,6 +254,63 @@ mod tests {
         assert!(config.extra_flags.contains(&"-Wall".to_string()));
     }
 
+    #[test]
+    fn test_with_toolchain() {
+        let toolchain = Toolchain {
+            clang: PathBuf::from("/opt/llvm/bin/clang-17"),
+            clangxx: PathBuf::from("/opt/llvm/bin/clang++-17"),
+            ..Toolchain::default()
+        };
+        let config = CompilerConfig::new(Language::C).with_toolchain(&toolchain);
+        assert_eq!(config.compiler_path, "/opt/llvm/bin/clang-17");
+        assert_eq!(config.cxx_driver(), "/opt/llvm/bin/clang++-17");
+
+        let config = CompilerConfig::new(Language::Cpp).with_toolchain(&toolchain);
+        assert_eq!(config.compiler_path, "/opt/llvm/bin/clang++-17");
+    }
+
+    #[test]
+    fn test_mcdc_flag() {
+        let config = CompilerConfig::new(Language::C);
+        assert!(!config.instrumentation_flags().contains(&"-fcoverage-mcdc".to_string()));
+        let config = config.with_mcdc();
+        assert!(config.instrumentation_flags().contains(&"-fcoverage-mcdc".to_string()));
+    }
+
+    #[test]
+    fn test_cxx_driver() {
+        let mut config = CompilerConfig::new(Language::C);
+        assert_eq!(config.cxx_driver(), "clang++");
+        config.compiler_path = "/usr/bin/clang-17".to_string();
+        assert_eq!(config.cxx_driver(), "/usr/bin/clang++-17");
+        assert_eq!(CompilerConfig::new(Language::Cpp).cxx_driver(), "clang++");
+    }
+
+    #[test]
+    fn test_incompatible_sanitizers_rejected() {
+        let dir = tempdir().unwrap();
+        let config = CompilerConfig::new(Language::C)
+            .with_sanitizers(vec![Sanitizer::Address, Sanitizer::Thread]);
+
+        let err = config
+            .compile_with_coverage(&dir.path().join("missing.c"), &dir.path().join("out"))
+            .unwrap_err();
+        assert!(matches!(err, CompileError::IncompatibleSanitizers { .. }));
+    }
+
+    #[test]
+    fn test_missing_compiler() {
+        let dir = tempdir().unwrap();
+        let mut config = CompilerConfig::new(Language::C);
+        config.compiler_path = "no-such-clang-for-fuzz-suite".to_string();
+
+        let err = config
+            .compile_with_coverage(&dir.path().join("test.c"), &dir.path().join("test"))
+            .unwrap_err();
+        assert!(matches!(err, CompileError::CompilerNotFound { ref compiler }
+            if compiler == "no-such-clang-for-fuzz-suite"));
+    }
+
     #[test]
     fn test_compile_c_program() -> io::Result<()> {
         let dir = tempdir()?;
@@ -0,0 +1,535 @@
+//! Control-flow and call graphs of a target, read from the textual LLVM IR clang emits
+//! with `-S -emit-llvm -g`, and rendered as Graphviz DOT colored by coverage.
+//!
+//! Basic blocks are matched to coverage by the line of their first instruction with a
+//! debug location, so a block's count is the count of that source line.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use super::compiler::{compilation_failed, CompilerConfig};
+use super::coverage::CoverageData;
+use super::error::CompileError;
+use super::target::{BuildTarget, TranslationUnit};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BasicBlock {
+    pub label: String,
+    /// Source line of the first instruction with debug info
+    pub line: Option<u32>,
+    pub successors: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionCfg {
+    pub name: String,
+    /// In IR order, the entry block first
+    pub blocks: Vec<BasicBlock>,
+    /// Functions called from this one, in order of first call
+    pub calls: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ControlFlowGraph {
+    pub functions: Vec<FunctionCfg>,
+}
+
+impl ControlFlowGraph {
+    /// Compile `source` to LLVM IR and extract the graphs of every function it defines
+    pub fn extract(source: &Path, config: &CompilerConfig) -> Result<Self, CompileError> {
+        Self::extract_unit(&BuildTarget::new(), &TranslationUnit::new(source), config)
+    }
+
+    /// Like `extract`, for one unit of a multi-file target: compiled with the target's
+    /// include directories and defines and the unit's own flags, from its directory
+    pub fn extract_unit(
+        target: &BuildTarget,
+        unit: &TranslationUnit,
+        config: &CompilerConfig,
+    ) -> Result<Self, CompileError> {
+        let mut command = Command::new(&config.compiler_path);
+        command
+            .arg(&unit.source)
+            .args(["-S", "-emit-llvm", "-g", "-O0", "-o", "-"])
+            .args(target.source_flags(unit))
+            .args(&config.extra_flags);
+        if let Some(dir) = &unit.directory {
+            command.current_dir(dir);
+        }
+        let output = command
+            .output()
+            .map_err(|e| CompileError::from_spawn(&config.compiler_path, e))?;
+
+        if !output.status.success() {
+            return Err(compilation_failed(&unit.source, &output));
+        }
+        Ok(Self::from_llvm_ir(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// Parse the function definitions of a textual LLVM IR module
+    pub fn from_llvm_ir(ir: &str) -> Self {
+        let locations = debug_locations(ir);
+        let mut functions = Vec::new();
+        let mut current: Option<FunctionCfg> = None;
+
+        for line in ir.lines() {
+            let trimmed = line.trim();
+            if line.starts_with("define ") {
+                if let Some(name) = global_name(line) {
+                    current = Some(FunctionCfg {
+                        name,
+                        blocks: Vec::new(),
+                        calls: Vec::new(),
+                    });
+                }
+                continue;
+            }
+            let Some(function) = current.as_mut() else {
+                continue;
+            };
+            if line.starts_with('}') {
+                functions.extend(current.take());
+                continue;
+            }
+            if trimmed.is_empty() || trimmed.starts_with(';') {
+                continue;
+            }
+
+            if !line.starts_with(' ') {
+                if let Some(label) = block_label(trimmed) {
+                    function.blocks.push(new_block(label));
+                    continue;
+                }
+            }
+            // The entry block has no label of its own
+            if function.blocks.is_empty() {
+                function.blocks.push(new_block("entry".to_string()));
+            }
+            let block = function.blocks.last_mut().expect("block was just pushed");
+
+            if block.line.is_none() {
+                block.line = dbg_id(trimmed).and_then(|id| locations.get(&id).copied());
+            }
+            for target in label_operands(trimmed) {
+                if !block.successors.contains(&target) {
+                    block.successors.push(target);
+                }
+            }
+            if let Some(callee) = callee(trimmed) {
+                if !function.calls.contains(&callee) {
+                    function.calls.push(callee);
+                }
+            }
+        }
+
+        ControlFlowGraph { functions }
+    }
+
+    /// Control-flow graph of every function as DOT, one cluster per function. Blocks
+    /// are colored by the hit count of their line in `file`; frontier edges, where a
+    /// block that ran has a successor that never did, are drawn in bold red.
+    pub fn cfg_dot(&self, coverage_data: &CoverageData, file: &Path) -> String {
+        let mut dot = String::from("digraph cfg {\n  node [shape=box, style=filled, fontname=\"monospace\"];\n");
+
+        for (index, function) in self.functions.iter().enumerate() {
+            let _ = writeln!(dot, "  subgraph cluster_{} {{\n    label=\"{}\";", index, escape_dot(&function.name));
+            let counts: HashMap<&str, Option<u64>> = function
+                .blocks
+                .iter()
+                .map(|block| {
+                    let count = block.line.and_then(|line| coverage_data.line_count(file, line));
+                    (block.label.as_str(), count)
+                })
+                .collect();
+
+            for block in &function.blocks {
+                let count = counts[block.label.as_str()];
+                let mut label = escape_dot(&block.label);
+                if let Some(line) = block.line {
+                    let _ = write!(label, "\\nline {}", line);
+                }
+                if let Some(count) = count {
+                    let _ = write!(label, "\\n{} hits", count);
+                }
+                let _ = writeln!(
+                    dot,
+                    "    \"{}\" [label=\"{}\", fillcolor=\"{}\"];",
+                    escape_dot(&node_id(&function.name, &block.label)),
+                    label,
+                    heat_color(count)
+                );
+            }
+
+            for block in &function.blocks {
+                let reached = counts[block.label.as_str()].is_some_and(|count| count > 0);
+                for successor in &block.successors {
+                    let frontier = reached
+                        && block.successors.len() > 1
+                        && counts.get(successor.as_str()).copied().flatten() == Some(0);
+                    let _ = writeln!(
+                        dot,
+                        "    \"{}\" -> \"{}\"{};",
+                        escape_dot(&node_id(&function.name, &block.label)),
+                        escape_dot(&node_id(&function.name, successor)),
+                        if frontier { FRONTIER_STYLE } else { "" }
+                    );
+                }
+            }
+            dot.push_str("  }\n");
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Call graph as DOT, functions colored by how often they were called. Calls from a
+    /// function that ran to one that never did are drawn in bold red.
+    pub fn call_graph_dot(&self, coverage_data: &CoverageData) -> String {
+        let mut dot = String::from("digraph calls {\n  node [shape=box, style=filled, fontname=\"monospace\"];\n");
+        // llvm-cov names static functions `file.c:name`, the IR just `name`
+        let mut called: HashMap<&str, u64> = HashMap::new();
+        for (name, function) in &coverage_data.functions {
+            *called.entry(bare_function_name(name)).or_insert(0) += function.called;
+        }
+        let calls = |name: &str| called.get(name).copied();
+
+        let mut names: BTreeSet<&str> = BTreeSet::new();
+        for function in &self.functions {
+            names.insert(&function.name);
+            names.extend(function.calls.iter().map(String::as_str));
+        }
+        for name in names {
+            let label = match calls(name) {
+                Some(count) => format!("{}\\n{} calls", escape_dot(name), count),
+                None => escape_dot(name),
+            };
+            let _ = writeln!(
+                dot,
+                "  \"{}\" [label=\"{}\", fillcolor=\"{}\"];",
+                escape_dot(name),
+                label,
+                heat_color(calls(name))
+            );
+        }
+
+        for function in &self.functions {
+            let reached = calls(&function.name).is_some_and(|count| count > 0);
+            for callee in &function.calls {
+                let frontier = reached && calls(callee) == Some(0);
+                let _ = writeln!(
+                    dot,
+                    "  \"{}\" -> \"{}\"{};",
+                    escape_dot(&function.name),
+                    escape_dot(callee),
+                    if frontier { FRONTIER_STYLE } else { "" }
+                );
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Function name without the `file:` prefix llvm-cov gives functions with internal
+/// linkage. C++ names keep their `::` scopes.
+fn bare_function_name(name: &str) -> &str {
+    match name.split_once(':') {
+        Some((file, rest)) if !file.is_empty() && !rest.starts_with(':') => rest,
+        _ => name,
+    }
+}
+
+const FRONTIER_STYLE: &str = " [color=\"red\", penwidth=3]";
+
+/// Render DOT to SVG with Graphviz
+pub fn render_dot_svg(dot: &str, output_path: &Path) -> io::Result<()> {
+    let mut child = Command::new("dot")
+        .arg("-Tsvg")
+        .arg("-o")
+        .arg(output_path)
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            if e.kind() == io::ErrorKind::NotFound {
+                io::Error::new(e.kind(), "Graphviz `dot` not found")
+            } else {
+                e
+            }
+        })?;
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(dot.as_bytes())?;
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "dot failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+fn new_block(label: String) -> BasicBlock {
+    BasicBlock {
+        label,
+        line: None,
+        successors: Vec::new(),
+    }
+}
+
+fn node_id(function: &str, block: &str) -> String {
+    format!("{}:{}", function, block)
+}
+
+/// Fill color for a hit count: grey without coverage data, red if never run, and
+/// deeper greens the hotter the code
+fn heat_color(count: Option<u64>) -> &'static str {
+    match count {
+        None => "#eeeeee",
+        Some(0) => "#f8c8c8",
+        Some(1..=9) => "#d6f5d6",
+        Some(10..=999) => "#9ddc9d",
+        Some(_) => "#5cbf5c",
+    }
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// `!N = !DILocation(line: L, ...)` entries, by metadata id
+fn debug_locations(ir: &str) -> HashMap<String, u32> {
+    ir.lines()
+        .filter_map(|line| {
+            let (id, rest) = line.split_once(" = ")?;
+            let rest = rest.trim_start_matches("distinct ");
+            let fields = rest.strip_prefix("!DILocation(")?;
+            let line = fields.split_once("line: ")?.1;
+            let digits: String = line.chars().take_while(char::is_ascii_digit).collect();
+            Some((id.trim().to_string(), digits.parse().ok()?))
+        })
+        .collect()
+}
+
+/// The `!N` of an instruction's `!dbg !N` attachment
+fn dbg_id(instruction: &str) -> Option<String> {
+    let rest = instruction.split_once("!dbg ")?.1;
+    Some(rest.split(|c: char| c == ',' || c.is_whitespace()).next()?.to_string())
+}
+
+/// Name of the global defined or called, e.g. `check` in `define i32 @check(i32 %0)`
+fn global_name(line: &str) -> Option<String> {
+    let rest = line.split_once('@')?.1;
+    if let Some(quoted) = rest.strip_prefix('"') {
+        return Some(quoted.split_once('"')?.0.to_string());
+    }
+    let name: String = rest
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '$' | '-'))
+        .collect();
+    (!name.is_empty()).then_some(name)
+}
+
+/// Label of a block header line such as `5:` or `for.body:  ; preds = %3`
+fn block_label(line: &str) -> Option<String> {
+    let head = line.split(';').next()?.trim_end();
+    let label = head.strip_suffix(':')?;
+    let label = label.trim_matches('"');
+    (!label.is_empty() && !label.contains(' ')).then(|| label.to_string())
+}
+
+/// Every `label %X` operand, the successors named by a terminator
+fn label_operands(instruction: &str) -> Vec<String> {
+    instruction
+        .split("label %")
+        .skip(1)
+        .filter_map(|rest| {
+            let rest = rest.trim_start_matches('"');
+            let name: String = rest
+                .chars()
+                .take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '$' | '-'))
+                .collect();
+            (!name.is_empty()).then_some(name)
+        })
+        .collect()
+}
+
+/// Function called directly by a `call` or `invoke`, ignoring LLVM intrinsics
+fn callee(instruction: &str) -> Option<String> {
+    let start = instruction
+        .find("call ")
+        .or_else(|| instruction.find("invoke "))?;
+    let name = global_name(&instruction[start..])?;
+    (!name.starts_with("llvm.")).then_some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FunctionInfo, Location};
+    use std::path::PathBuf;
+
+    // Trimmed `clang -S -emit-llvm -g -O0` output for:
+    //  1 int check(int x) {
+    //  2   if (x > 5)
+    //  3     return 1;
+    //  4   return 0;
+    //  5 }
+    //  6
+    //  7 int main() {
+    //  8   return check(1);
+    //  9 }
+    const IR: &str = r#"
+define dso_local i32 @check(i32 noundef %0) #0 !dbg !10 {
+  %2 = alloca i32, align 4
+  %3 = alloca i32, align 4
+  store i32 %0, ptr %3, align 4
+  call void @llvm.dbg.declare(metadata ptr %3, metadata !16, metadata !DIExpression()), !dbg !17
+  %4 = load i32, ptr %3, align 4, !dbg !18
+  %5 = icmp sgt i32 %4, 5, !dbg !20
+  br i1 %5, label %6, label %7, !dbg !21
+
+6:                                                ; preds = %1
+  store i32 1, ptr %2, align 4, !dbg !22
+  br label %8, !dbg !22
+
+7:                                                ; preds = %1
+  store i32 0, ptr %2, align 4, !dbg !23
+  br label %8, !dbg !23
+
+8:                                                ; preds = %7, %6
+  %9 = load i32, ptr %2, align 4, !dbg !24
+  ret i32 %9, !dbg !24
+}
+
+define dso_local i32 @main() #0 !dbg !25 {
+  %1 = call i32 @check(i32 noundef 1), !dbg !28
+  ret i32 %1, !dbg !29
+}
+
+!17 = !DILocation(line: 1, column: 15, scope: !10)
+!18 = !DILocation(line: 2, column: 7, scope: !19)
+!20 = !DILocation(line: 2, column: 9, scope: !19)
+!21 = !DILocation(line: 2, column: 7, scope: !10)
+!22 = !DILocation(line: 3, column: 5, scope: !19)
+!23 = !DILocation(line: 4, column: 3, scope: !10)
+!24 = !DILocation(line: 5, column: 1, scope: !10)
+!28 = !DILocation(line: 8, column: 10, scope: !25)
+!29 = !DILocation(line: 8, column: 3, scope: !25)
+"#;
+
+    fn coverage() -> CoverageData {
+        let mut data = CoverageData::default();
+        for (line, count) in [(1, 1), (2, 1), (3, 0), (4, 1), (5, 1), (8, 1)] {
+            data.lines.insert(
+                Location {
+                    file: PathBuf::from("/src/test.c"),
+                    line,
+                    column: 0,
+                },
+                count,
+            );
+        }
+        for (name, called) in [("main", 1), ("check", 1)] {
+            data.functions.insert(
+                name.to_string(),
+                FunctionInfo {
+                    name: name.to_string(),
+                    file: PathBuf::from("/src/test.c"),
+                    start_line: 1,
+                    end_line: 5,
+                    called,
+                },
+            );
+        }
+        data
+    }
+
+    #[test]
+    fn test_parse_ir() {
+        let graph = ControlFlowGraph::from_llvm_ir(IR);
+        assert_eq!(graph.functions.len(), 2);
+
+        let check = &graph.functions[0];
+        assert_eq!(check.name, "check");
+        let labels: Vec<&str> = check.blocks.iter().map(|b| b.label.as_str()).collect();
+        assert_eq!(labels, vec!["entry", "6", "7", "8"]);
+        assert_eq!(check.blocks[0].line, Some(1));
+        assert_eq!(check.blocks[0].successors, vec!["6", "7"]);
+        assert_eq!(check.blocks[1].line, Some(3));
+        assert!(check.calls.is_empty());
+
+        assert_eq!(graph.functions[1].calls, vec!["check"]);
+    }
+
+    #[test]
+    fn test_cfg_dot_marks_frontier() {
+        let graph = ControlFlowGraph::from_llvm_ir(IR);
+        let dot = graph.cfg_dot(&coverage(), Path::new("/src/test.c"));
+
+        assert!(dot.contains("\"check:6\" [label=\"6\\nline 3\\n0 hits\", fillcolor=\"#f8c8c8\"];"));
+        assert!(dot.contains("\"check:entry\" -> \"check:6\" [color=\"red\", penwidth=3];"));
+        assert!(dot.contains("\"check:entry\" -> \"check:7\";"));
+        assert!(dot.contains("\"check:6\" -> \"check:8\";"));
+    }
+
+    #[test]
+    fn test_call_graph_dot() {
+        let graph = ControlFlowGraph::from_llvm_ir(IR);
+        let mut data = coverage();
+        data.functions.get_mut("check").unwrap().called = 0;
+
+        let dot = graph.call_graph_dot(&data);
+        assert!(dot.contains("\"main\" [label=\"main\\n1 calls\", fillcolor=\"#d6f5d6\"];"));
+        assert!(dot.contains("\"main\" -> \"check\" [color=\"red\", penwidth=3];"));
+    }
+
+    #[test]
+    fn test_call_graph_static_functions() {
+        let graph = ControlFlowGraph::from_llvm_ir(IR);
+        let mut data = coverage();
+        let mut check = data.functions.remove("check").unwrap();
+        check.called = 4;
+        data.functions.insert("test.c:check".to_string(), check);
+
+        let dot = graph.call_graph_dot(&data);
+        assert!(dot.contains("\"check\" [label=\"check\\n4 calls\""));
+        assert_eq!(bare_function_name("ns::f"), "ns::f");
+        assert_eq!(bare_function_name("src/a.c:ns::f"), "ns::f");
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn test_extract_unit_passes_target_flags() -> Result<(), CompileError> {
+        use std::os::unix::fs::PermissionsExt;
+
+        // Stand-in compiler that only emits IR when given the target's include dir and define
+        let dir = tempfile::tempdir()?;
+        let fake_cc = dir.path().join("fake-cc");
+        std::fs::write(
+            &fake_cc,
+            "#!/bin/sh\ncase \"$*\" in *-I*/include*-DLEVEL=2*) ;; *) exit 1 ;; esac\n\
+             printf 'define i32 @f() {\\n  ret i32 0\\n}\\n'\n",
+        )?;
+        std::fs::set_permissions(&fake_cc, std::fs::Permissions::from_mode(0o755))?;
+        let mut config = CompilerConfig::new(crate::Language::C);
+        config.compiler_path = fake_cc.to_string_lossy().into_owned();
+
+        let unit = TranslationUnit::new(Path::new("a.c"));
+        let target = BuildTarget::new()
+            .with_include_dirs(vec![dir.path().join("include")])
+            .with_defines(vec!["LEVEL=2".to_string()]);
+        let graph = ControlFlowGraph::extract_unit(&target, &unit, &config)?;
+        assert_eq!(graph.functions[0].name, "f");
+        assert!(ControlFlowGraph::extract(Path::new("a.c"), &config).is_err());
+        Ok(())
+    }
+}
@@ -1,9 +1,16 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::io;
 use walkdir::WalkDir;
 
+use super::error::CompileError;
+use super::lcov::parse_lcov;
+use super::llvm_export::parse_llvm_export;
+
+/// Lines of source files by path, `None` for files that couldn't be read
+pub(crate) type SourceLines = HashMap<PathBuf, Option<Vec<String>>>;
+
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
 pub struct Location {
     pub file: PathBuf,
@@ -13,30 +20,594 @@ pub struct Location {
 
 #[derive(Debug, Clone)]
 pub struct BranchInfo {
-    pub taken: bool,
-    pub count: u64,
+    pub true_count: u64,
+    pub false_count: u64,
+    pub end_line: u32,
+    pub end_column: u32,
     pub condition: Option<String>, // For C/C++ conditions
 }
 
+impl BranchInfo {
+    /// Whether the condition was evaluated at all
+    pub fn reached(&self) -> bool {
+        self.true_count > 0 || self.false_count > 0
+    }
+
+    pub fn count(&self, arm: BranchArm) -> u64 {
+        match arm {
+            BranchArm::True => self.true_count,
+            BranchArm::False => self.false_count,
+        }
+    }
+}
+
+/// One outcome of a branch condition
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum BranchArm {
+    True,
+    False,
+}
+
+impl BranchArm {
+    pub const ALL: [BranchArm; 2] = [BranchArm::True, BranchArm::False];
+
+    /// Branch number used in LCOV `BRDA` records
+    pub fn id(self) -> usize {
+        match self {
+            BranchArm::True => 0,
+            BranchArm::False => 1,
+        }
+    }
+}
+
+/// One arm of a branch condition, the unit branch coverage is counted in
+#[derive(Debug, Clone, PartialEq)]
+pub struct BranchEdge {
+    pub location: Location,
+    /// Index of the condition among those on the same line, in column order
+    pub block: usize,
+    pub arm: BranchArm,
+    pub count: u64,
+    /// Whether the condition was evaluated at all
+    pub reached: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct FunctionInfo {
     pub name: String,
+    pub file: PathBuf,
     pub start_line: u32,
     pub end_line: u32,
     pub called: u64,
 }
 
+/// MC/DC record for one decision (a boolean expression with several conditions)
 #[derive(Debug, Clone)]
+pub struct McdcInfo {
+    pub end_line: u32,
+    pub end_column: u32,
+    /// Whether an independence pair was covered for each condition, in source order
+    pub independence_pairs: Vec<bool>,
+}
+
+impl McdcInfo {
+    pub fn conditions(&self) -> usize {
+        self.independence_pairs.len()
+    }
+
+    pub fn covered_conditions(&self) -> usize {
+        self.independence_pairs.iter().filter(|covered| **covered).count()
+    }
+}
+
+/// A code region from the coverage mapping with its execution count
+#[derive(Debug, Clone)]
+pub struct RegionInfo {
+    pub start: Location,
+    pub end_line: u32,
+    pub end_column: u32,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct CoverageData {
-    pub lines: HashSet<Location>,
+    /// Execution count of every executable line, including lines never run.
+    /// The column is 0 since these describe whole lines.
+    pub lines: HashMap<Location, u64>,
     pub branches: HashMap<Location, BranchInfo>,
     pub functions: HashMap<String, FunctionInfo>,
+    pub regions: Vec<RegionInfo>,
+    /// MC/DC decisions, keyed by the start of the decision
+    pub mcdc: HashMap<Location, McdcInfo>,
     pub includes: HashSet<PathBuf>,  // Track included files
 }
 
+impl CoverageData {
+    /// Read coverage from an LCOV tracefile or an `llvm-cov export` JSON file
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        if text.trim_start().starts_with('{') {
+            parse_llvm_export(&text)
+        } else {
+            parse_lcov(&text)
+        }
+    }
+
+    /// Fill in `BranchInfo::condition` with the source text of each condition, reading
+    /// the files the coverage refers to. Files that can't be read are skipped.
+    pub fn read_branch_conditions(&mut self) {
+        self.read_branch_conditions_cached(&mut HashMap::new());
+    }
+
+    /// `read_branch_conditions`, keeping the lines of every file read in `sources` so
+    /// later calls don't read them again
+    pub(crate) fn read_branch_conditions_cached(&mut self, sources: &mut SourceLines) {
+        for (location, info) in self.branches.iter_mut().filter(|(_, info)| info.condition.is_none()) {
+            let lines = sources.entry(location.file.clone()).or_insert_with(|| {
+                std::fs::read_to_string(&location.file)
+                    .ok()
+                    .map(|text| text.lines().map(str::to_string).collect())
+            });
+            if let Some(lines) = lines {
+                info.condition = source_span(lines, location, info.end_line, info.end_column);
+            }
+        }
+    }
+
+    /// Number of executable lines
+    pub fn total_lines(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Number of executable lines run at least once
+    pub fn covered_lines(&self) -> usize {
+        self.lines.values().filter(|count| **count > 0).count()
+    }
+
+    /// Execution count of a line, `None` if the line has no code
+    pub fn line_count(&self, file: &Path, line: u32) -> Option<u64> {
+        self.lines
+            .get(&Location {
+                file: file.to_path_buf(),
+                line,
+                column: 0,
+            })
+            .copied()
+    }
+
+    /// Per-file totals, taken from the lines, regions and functions LLVM instrumented
+    pub fn file_summaries(&self) -> BTreeMap<PathBuf, FileSummary> {
+        let mut summaries: BTreeMap<PathBuf, FileSummary> = BTreeMap::new();
+        for (location, count) in &self.lines {
+            let summary = summaries.entry(location.file.clone()).or_default();
+            summary.total_lines += 1;
+            summary.covered_lines += usize::from(*count > 0);
+        }
+        for region in &self.regions {
+            let summary = summaries.entry(region.start.file.clone()).or_default();
+            summary.total_regions += 1;
+            summary.covered_regions += usize::from(region.count > 0);
+        }
+        for (location, info) in &self.branches {
+            let summary = summaries.entry(location.file.clone()).or_default();
+            summary.total_branches += 2;
+            summary.covered_branches +=
+                BranchArm::ALL.iter().filter(|arm| info.count(**arm) > 0).count();
+        }
+        for (location, decision) in &self.mcdc {
+            let summary = summaries.entry(location.file.clone()).or_default();
+            summary.total_conditions += decision.conditions();
+            summary.covered_conditions += decision.covered_conditions();
+        }
+        for function in self.functions.values() {
+            let summary = summaries.entry(function.file.clone()).or_default();
+            summary.total_functions += 1;
+            summary.covered_functions += usize::from(function.called > 0);
+        }
+        summaries
+    }
+
+    /// Totals over all files
+    pub fn summary(&self) -> FileSummary {
+        let mut total = FileSummary::default();
+        for summary in self.file_summaries().values() {
+            total.add(summary);
+        }
+        total
+    }
+
+    /// Drop everything belonging to files the predicate rejects
+    pub fn retain_files<F: Fn(&Path) -> bool>(&mut self, keep: F) {
+        self.lines.retain(|location, _| keep(&location.file));
+        self.branches.retain(|location, _| keep(&location.file));
+        self.functions.retain(|_, function| keep(&function.file));
+        self.regions.retain(|region| keep(&region.start.file));
+        self.mcdc.retain(|location, _| keep(&location.file));
+    }
+
+    /// Both arms of every branch condition, ordered by file, line and column
+    pub fn branch_edges(&self) -> Vec<BranchEdge> {
+        let mut branches: Vec<(&Location, &BranchInfo)> = self.branches.iter().collect();
+        branches.sort_by(|a, b| {
+            (&a.0.file, a.0.line, a.0.column).cmp(&(&b.0.file, b.0.line, b.0.column))
+        });
+
+        let mut edges = Vec::with_capacity(branches.len() * 2);
+        let mut block = 0;
+        let mut previous: Option<&Location> = None;
+        for (location, info) in branches {
+            block = match previous {
+                Some(prev) if prev.file == location.file && prev.line == location.line => block + 1,
+                _ => 0,
+            };
+            previous = Some(location);
+            for arm in BranchArm::ALL {
+                edges.push(BranchEdge {
+                    location: location.clone(),
+                    block,
+                    arm,
+                    count: info.count(arm),
+                    reached: info.reached(),
+                });
+            }
+        }
+        edges
+    }
+
+    /// Number of branch arms and how many were taken at least once
+    pub fn branch_arms(&self) -> (usize, usize) {
+        self.branches.values().fold((0, 0), |(total, covered), info| {
+            let taken = BranchArm::ALL.iter().filter(|arm| info.count(**arm) > 0).count();
+            (total + 2, covered + taken)
+        })
+    }
+
+    /// Total number of MC/DC conditions and how many had an independence pair covered
+    pub fn mcdc_conditions(&self) -> (usize, usize) {
+        self.mcdc.values().fold((0, 0), |(total, covered), decision| {
+            (total + decision.conditions(), covered + decision.covered_conditions())
+        })
+    }
+
+    /// Executed lines, ordered by file and line
+    pub fn covered_line_locations(&self) -> Vec<&Location> {
+        let mut locations: Vec<&Location> = self
+            .lines
+            .iter()
+            .filter(|(_, count)| **count > 0)
+            .map(|(location, _)| location)
+            .collect();
+        locations.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+        locations
+    }
+
+    /// Coverage of both runs combined: everything either executed, with counts added up
+    pub fn union(&self, other: &CoverageData) -> CoverageData {
+        let mut result = self.clone();
+        for (location, count) in &other.lines {
+            *result.lines.entry(location.clone()).or_insert(0) += count;
+        }
+        let ours = by_condition(&self.branches);
+        for (key, (location, info)) in by_condition(&other.branches) {
+            let location = ours.get(&key).map_or(location, |(ours, _)| *ours);
+            let merged = result.branches.entry(location.clone()).or_insert_with(|| BranchInfo {
+                true_count: 0,
+                false_count: 0,
+                ..info.clone()
+            });
+            merged.true_count += info.true_count;
+            merged.false_count += info.false_count;
+        }
+        for (name, function) in &other.functions {
+            result
+                .functions
+                .entry(name.clone())
+                .or_insert_with(|| FunctionInfo {
+                    called: 0,
+                    ..function.clone()
+                })
+                .called += function.called;
+        }
+        let ours = by_condition(&self.mcdc);
+        for (key, (location, decision)) in by_condition(&other.mcdc) {
+            let location = ours.get(&key).map_or(location, |(ours, _)| *ours);
+            let merged = result.mcdc.entry(location.clone()).or_insert_with(|| McdcInfo {
+                independence_pairs: vec![false; decision.conditions()],
+                ..decision.clone()
+            });
+            for (merged, covered) in merged.independence_pairs.iter_mut().zip(&decision.independence_pairs) {
+                *merged |= covered;
+            }
+        }
+        let ours: HashMap<(&Location, u32, u32), usize> = self
+            .regions
+            .iter()
+            .enumerate()
+            .map(|(index, region)| ((&region.start, region.end_line, region.end_column), index))
+            .collect();
+        for region in &other.regions {
+            match ours.get(&(&region.start, region.end_line, region.end_column)) {
+                Some(&index) => result.regions[index].count += region.count,
+                None => result.regions.push(region.clone()),
+            }
+        }
+        result.includes.extend(other.includes.iter().cloned());
+        result
+    }
+
+    /// What both runs executed: code instrumented in both, covered only where both
+    /// covered it (counts are the smaller of the two)
+    pub fn intersection(&self, other: &CoverageData) -> CoverageData {
+        let their_branches = by_condition(&other.branches);
+        let their_mcdc = by_condition(&other.mcdc);
+        let their_regions = other.region_counts();
+        CoverageData {
+            lines: self
+                .lines
+                .iter()
+                .filter_map(|(location, count)| {
+                    let theirs = other.lines.get(location)?;
+                    Some((location.clone(), (*count).min(*theirs)))
+                })
+                .collect(),
+            branches: by_condition(&self.branches)
+                .into_iter()
+                .filter_map(|(key, (location, info))| {
+                    let (_, theirs) = their_branches.get(&key)?;
+                    Some((
+                        location.clone(),
+                        BranchInfo {
+                            true_count: info.true_count.min(theirs.true_count),
+                            false_count: info.false_count.min(theirs.false_count),
+                            ..info.clone()
+                        },
+                    ))
+                })
+                .collect(),
+            functions: self
+                .functions
+                .iter()
+                .filter_map(|(name, function)| {
+                    let theirs = other.functions.get(name)?;
+                    Some((
+                        name.clone(),
+                        FunctionInfo {
+                            called: function.called.min(theirs.called),
+                            ..function.clone()
+                        },
+                    ))
+                })
+                .collect(),
+            regions: self
+                .regions
+                .iter()
+                .filter_map(|region| {
+                    let theirs = their_regions(region)?;
+                    Some(RegionInfo {
+                        count: region.count.min(theirs),
+                        ..region.clone()
+                    })
+                })
+                .collect(),
+            mcdc: by_condition(&self.mcdc)
+                .into_iter()
+                .filter_map(|(key, (location, decision))| {
+                    let (_, theirs) = their_mcdc.get(&key)?;
+                    let mut decision = decision.clone();
+                    for (covered, theirs) in decision.independence_pairs.iter_mut().zip(&theirs.independence_pairs) {
+                        *covered &= theirs;
+                    }
+                    Some((location.clone(), decision))
+                })
+                .collect(),
+            includes: self.includes.intersection(&other.includes).cloned().collect(),
+        }
+    }
+
+    /// What this run executed that `other` never did, e.g. the lines only the fuzzer
+    /// reached. All of this run's code is kept; anything `other` covered counts as
+    /// not executed.
+    pub fn difference(&self, other: &CoverageData) -> CoverageData {
+        let unless = |count: u64, theirs: Option<u64>| {
+            if theirs.unwrap_or(0) > 0 { 0 } else { count }
+        };
+        let their_branches = by_condition(&other.branches);
+        let their_mcdc = by_condition(&other.mcdc);
+        let their_regions = other.region_counts();
+        CoverageData {
+            lines: self
+                .lines
+                .iter()
+                .map(|(location, count)| {
+                    (location.clone(), unless(*count, other.lines.get(location).copied()))
+                })
+                .collect(),
+            branches: by_condition(&self.branches)
+                .into_iter()
+                .map(|(key, (location, info))| {
+                    let theirs = their_branches.get(&key).map(|(_, theirs)| *theirs);
+                    (
+                        location.clone(),
+                        BranchInfo {
+                            true_count: unless(info.true_count, theirs.map(|t| t.true_count)),
+                            false_count: unless(info.false_count, theirs.map(|t| t.false_count)),
+                            ..info.clone()
+                        },
+                    )
+                })
+                .collect(),
+            functions: self
+                .functions
+                .iter()
+                .map(|(name, function)| {
+                    let theirs = other.functions.get(name).map(|f| f.called);
+                    (
+                        name.clone(),
+                        FunctionInfo {
+                            called: unless(function.called, theirs),
+                            ..function.clone()
+                        },
+                    )
+                })
+                .collect(),
+            regions: self
+                .regions
+                .iter()
+                .map(|region| RegionInfo {
+                    count: unless(region.count, their_regions(region)),
+                    ..region.clone()
+                })
+                .collect(),
+            mcdc: by_condition(&self.mcdc)
+                .into_iter()
+                .map(|(key, (location, decision))| {
+                    let mut decision = decision.clone();
+                    if let Some((_, theirs)) = their_mcdc.get(&key) {
+                        for (covered, theirs) in decision.independence_pairs.iter_mut().zip(&theirs.independence_pairs) {
+                            *covered &= !theirs;
+                        }
+                    }
+                    (location.clone(), decision)
+                })
+                .collect(),
+            includes: self.includes.clone(),
+        }
+    }
+
+    /// Looks up the count of a region in this data: the region with the same extent,
+    /// or for LCOV data, which has no regions, the count of the line it starts on
+    fn region_counts(&self) -> impl Fn(&RegionInfo) -> Option<u64> + '_ {
+        let regions: HashMap<(&Location, u32, u32), u64> = self
+            .regions
+            .iter()
+            .map(|region| ((&region.start, region.end_line, region.end_column), region.count))
+            .collect();
+        move |region| {
+            if self.regions.is_empty() {
+                self.line_count(&region.start.file, region.start.line)
+            } else {
+                regions
+                    .get(&(&region.start, region.end_line, region.end_column))
+                    .copied()
+            }
+        }
+    }
+
+    /// The `n` most executed lines, hottest first
+    pub fn hot_spots(&self, n: usize) -> Vec<(&Location, u64)> {
+        let mut lines: Vec<(&Location, u64)> = self
+            .lines
+            .iter()
+            .filter(|(_, count)| **count > 0)
+            .map(|(location, count)| (location, *count))
+            .collect();
+        lines.sort_by(|a, b| {
+            b.1.cmp(&a.1)
+                .then_with(|| a.0.file.cmp(&b.0.file))
+                .then_with(|| a.0.line.cmp(&b.0.line))
+        });
+        lines.truncate(n);
+        lines
+    }
+}
+
+/// Source text from `start` up to, not including, `end_column` of `end_line`, with
+/// whitespace collapsed. Lines and columns are 1-based as in LLVM's coverage mapping.
+fn source_span(lines: &[String], start: &Location, end_line: u32, end_column: u32) -> Option<String> {
+    let mut text = String::new();
+    for line in start.line..=end_line {
+        let source = lines.get(line.checked_sub(1)? as usize)?;
+        let from = if line == start.line { start.column.saturating_sub(1) as usize } else { 0 };
+        let to = if line == end_line { end_column.saturating_sub(1) as usize } else { source.len() };
+        text.push_str(source.get(from..to.min(source.len()))?);
+        text.push(' ');
+    }
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!text.is_empty()).then_some(text)
+}
+
+/// Coverage totals for one source file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileSummary {
+    pub total_lines: usize,
+    pub covered_lines: usize,
+    pub total_regions: usize,
+    pub covered_regions: usize,
+    /// Branch arms, two per condition
+    pub total_branches: usize,
+    pub covered_branches: usize,
+    pub total_functions: usize,
+    pub covered_functions: usize,
+    pub total_conditions: usize,
+    pub covered_conditions: usize,
+}
+
+impl FileSummary {
+    pub fn line_coverage(&self) -> f64 {
+        percentage(self.covered_lines, self.total_lines)
+    }
+
+    pub fn branch_coverage(&self) -> f64 {
+        percentage(self.covered_branches, self.total_branches)
+    }
+
+    pub fn function_coverage(&self) -> f64 {
+        percentage(self.covered_functions, self.total_functions)
+    }
+
+    /// Accumulate another file's totals, e.g. for a directory or the whole program
+    pub fn add(&mut self, other: &FileSummary) {
+        self.total_lines += other.total_lines;
+        self.covered_lines += other.covered_lines;
+        self.total_regions += other.total_regions;
+        self.covered_regions += other.covered_regions;
+        self.total_branches += other.total_branches;
+        self.covered_branches += other.covered_branches;
+        self.total_functions += other.total_functions;
+        self.covered_functions += other.covered_functions;
+        self.total_conditions += other.total_conditions;
+        self.covered_conditions += other.covered_conditions;
+    }
+}
+
+/// Identifies a branch condition or MC/DC decision by file, line and its order among
+/// those on the line. LCOV has no columns, so this is what matches entries between LCOV
+/// and `llvm-cov export` data.
+pub(crate) type ConditionKey<'a> = (&'a Path, u32, usize);
+
+/// Entries of a map keyed by condition location, keyed by `ConditionKey` instead
+pub(crate) fn by_condition<V>(map: &HashMap<Location, V>) -> HashMap<ConditionKey<'_>, (&Location, &V)> {
+    let mut entries: Vec<(&Location, &V)> = map.iter().collect();
+    entries.sort_by(|a, b| (&a.0.file, a.0.line, a.0.column).cmp(&(&b.0.file, b.0.line, b.0.column)));
+
+    let mut keyed = HashMap::with_capacity(entries.len());
+    let mut order = 0;
+    let mut previous: Option<&Location> = None;
+    for (location, value) in entries {
+        order = match previous {
+            Some(prev) if prev.file == location.file && prev.line == location.line => order + 1,
+            _ => 0,
+        };
+        previous = Some(location);
+        keyed.insert((location.file.as_path(), location.line, order), (location, value));
+    }
+    keyed
+}
+
+/// `part` as a percentage of `total`, 0 when there is nothing to cover
+pub(crate) fn percentage(part: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 / total as f64 * 100.0
+    }
+}
+
 pub struct Coverage {
     config: super::CoverageConfig,
     data: CoverageData,
+    /// Sources read for branch conditions, which don't change during a campaign
+    sources: SourceLines,
 }
 
 impl Coverage {
@@ -44,117 +615,377 @@ impl Coverage {
         Coverage {
             config,
             data: CoverageData {
-                lines: HashSet::new(),
+                lines: HashMap::new(),
                 branches: HashMap::new(),
                 functions: HashMap::new(),
+                regions: Vec::new(),
+                mcdc: HashMap::new(),
                 includes: HashSet::new(),
             },
+            sources: HashMap::new(),
         }
     }
 
-    /// Process LLVM coverage data for C/C++ programs
-    pub fn process_coverage(&mut self) -> io::Result<()> {
+    /// Process LLVM coverage data for C/C++ programs.
+    ///
+    /// The latest raw profile is merged into the accumulated profile for the campaign,
+    /// so the data afterwards holds execution counts over every run so far. A missing
+    /// profile is reported as an `Io` error of kind `NotFound`.
+    pub fn process_coverage(&mut self) -> Result<(), CompileError> {
+        self.config.file_filter.validate()?;
+        let binary = self.config.binary.as_ref().ok_or_else(|| {
+            CompileError::InvalidConfig(
+                "no instrumented binary configured for coverage".to_string(),
+            )
+        })?;
+
         // First, find the raw profile data
         let raw_profile = self.config.output_dir.join("default.profraw");
-        if !raw_profile.exists() {
+        let profdata = self.config.output_dir.join("coverage.profdata");
+        if !raw_profile.exists() && !profdata.exists() {
             return Err(io::Error::new(
                 io::ErrorKind::NotFound,
                 format!("Raw profile data not found at {:?}", raw_profile)
-            ));
+            )
+            .into());
         }
 
+        let toolchain = &self.config.toolchain;
 
-        // Merge coverage profiles
-        let merge_status = Command::new("llvm-profdata")
-            .arg("merge")
-            .arg("-sparse")
-            .arg(&raw_profile)
-            .arg("-o")
-            .arg(self.config.output_dir.join("coverage.profdata"))
-            .status()?;
+        // Merge coverage profiles. A run that aborted (e.g. on a sanitizer report)
+        // writes no profile, in which case there's nothing new to merge.
+        if raw_profile.exists() {
+            let merged = self.config.output_dir.join("coverage.profdata.tmp");
+            let mut merge = Command::new(&toolchain.llvm_profdata);
+            merge.arg("merge").arg("-sparse").arg(&raw_profile);
+            if profdata.exists() {
+                merge.arg(&profdata);
+            }
+            let merge_status = merge
+                .arg("-o")
+                .arg(&merged)
+                .status()
+                .map_err(|e| tool_error(&toolchain.llvm_profdata, e))?;
 
-        if !merge_status.success() {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                "Failed to merge profile data"
-            ));
-        }
-
-        // Generate coverage information
-        let output = Command::new("llvm-cov")
-            .arg("show")
-            .arg("--format=text")
-            .arg("--show-expansions")  // Show macro expansions
-            .arg("--show-branches")    // Show branch coverage
-            .arg("--show-functions")   // Show function coverage
-            .arg("--instr-profile")
-            .arg(self.config.output_dir.join("coverage.profdata"))
+            if !merge_status.success() {
+                return Err(CompileError::LlvmToolFailed {
+                    tool: toolchain.llvm_profdata.display().to_string(),
+                    message: format!("merging profile data failed ({})", merge_status),
+                });
+            }
+            std::fs::rename(&merged, &profdata)?;
+            // Don't count this run again if the next one writes no profile
+            std::fs::remove_file(&raw_profile)?;
+        }
+
+        // Export line, branch, function and region coverage as JSON
+        let output = Command::new(&toolchain.llvm_cov)
+            .arg("export")
+            .arg("-format=text")
+            .arg("-instr-profile")
+            .arg(&profdata)
+            .arg(binary)
             .arg(&self.config.source_dir)
-            .output()?;
+            .output()
+            .map_err(|e| tool_error(&toolchain.llvm_cov, e))?;
+
+        if !output.status.success() {
+            return Err(CompileError::LlvmToolFailed {
+                tool: toolchain.llvm_cov.display().to_string(),
+                message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            });
+        }
 
-        self.parse_coverage_output(String::from_utf8_lossy(&output.stdout).as_ref())?;
+        let includes = std::mem::take(&mut self.data.includes);
+        self.data = parse_llvm_export(&String::from_utf8_lossy(&output.stdout))?;
+        self.data.retain_files(|path| self.config.file_filter.matches(path));
+        self.data.includes = includes;
+        self.data.read_branch_conditions_cached(&mut self.sources);
         self.process_includes()?;
         Ok(())
     }
 
+    /// Forget the profile accumulated by an earlier campaign in the same output directory
+    pub fn reset(&mut self) -> io::Result<()> {
+        for file in ["default.profraw", "coverage.profdata"] {
+            let path = self.config.output_dir.join(file);
+            if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+        }
+        self.data = CoverageData::default();
+        Ok(())
+    }
+
     /// Process included files to track their coverage
     fn process_includes(&mut self) -> io::Result<()> {
         for file in WalkDir::new(&self.config.source_dir)
             .into_iter()
             .filter_map(Result::ok)
-            .filter(|e| e.path().extension().map_or(false, |ext| ext == "h" || ext == "hpp"))
+            .filter(|e| e.path().extension().is_some_and(|ext| ext == "h" || ext == "hpp"))
         {
             self.data.includes.insert(file.path().to_path_buf());
         }
         Ok(())
     }
 
-    fn parse_coverage_output(&mut self, output: &str) -> io::Result<()> {
-        for line in output.lines() {
-            if let Some(coverage_info) = self.parse_coverage_line(line) {
-                self.data.lines.insert(coverage_info);
-            }
-        }
-        Ok(())
+    pub fn get_coverage_data(&self) -> &CoverageData {
+        &self.data
     }
 
-    fn parse_coverage_line(&self, line: &str) -> Option<Location> {
-        // Parse LLVM coverage output format for C/C++
-        let parts: Vec<&str> = line.split(':').collect();
-        if parts.len() >= 3 {
-            Some(Location {
-                file: PathBuf::from(parts[0]),
-                line: parts[1].parse().ok()?,
-                column: parts[2].parse().ok()?,
-            })
-        } else {
-            None
+    /// Percentage of the instrumented lines of all counted files that were executed
+    pub fn coverage_percentage(&self) -> f64 {
+        percentage(self.data.covered_lines(), self.data.total_lines())
+    }
+
+    /// Per-file coverage totals
+    pub fn file_coverage(&self) -> BTreeMap<PathBuf, FileSummary> {
+        self.data.file_summaries()
+    }
+}
+
+/// `LlvmToolNotFound` when an LLVM tool is not installed
+fn tool_error(tool: &Path, err: io::Error) -> CompileError {
+    CompileError::from_tool_spawn(&tool.to_string_lossy(), err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(file: &str, line: u32) -> Location {
+        Location {
+            file: PathBuf::from(file),
+            line,
+            column: 0,
         }
     }
 
-    pub fn get_coverage_data(&self) -> &CoverageData {
-        &self.data
+    #[test]
+    fn test_file_summaries() {
+        let mut data = CoverageData::default();
+        data.lines.insert(location("/p/a.c", 1), 2);
+        data.lines.insert(location("/p/a.c", 2), 0);
+        data.lines.insert(location("/p/lib/b.c", 7), 1);
+        data.regions.push(RegionInfo {
+            start: location("/p/a.c", 1),
+            end_line: 2,
+            end_column: 1,
+            count: 2,
+        });
+
+        let summaries = data.file_summaries();
+        let a = &summaries[Path::new("/p/a.c")];
+        assert_eq!((a.total_lines, a.covered_lines), (2, 1));
+        assert_eq!((a.total_regions, a.covered_regions), (1, 1));
+        assert_eq!(a.line_coverage(), 50.0);
+        assert_eq!(summaries[Path::new("/p/lib/b.c")].total_lines, 1);
     }
 
-    pub fn coverage_percentage(&self) -> f64 {
-        let total_lines = self.count_total_lines();
-        if total_lines == 0 {
-            return 0.0;
+    #[test]
+    fn test_union_merges_regions() {
+        let mut data = CoverageData::default();
+        for (line, count) in [(1, 2), (3, 0)] {
+            data.regions.push(RegionInfo {
+                start: location("/p/a.c", line),
+                end_line: line + 1,
+                end_column: 1,
+                count,
+            });
         }
-        (self.data.lines.len() as f64 / total_lines as f64) * 100.0
+
+        let both = data.union(&data);
+        assert_eq!(both.summary().total_regions, data.summary().total_regions);
+        assert_eq!(both.summary().covered_regions, 1);
+        assert_eq!(both.regions[0].count, 4);
     }
 
-    fn count_total_lines(&self) -> usize {
-        let mut total = 0;
-        if let Ok(entries) = std::fs::read_dir(&self.config.source_dir) {
-            for entry in entries.filter_map(Result::ok) {
-                if super::compiler::detect_language(entry.path().as_ref()).is_some() {
-                    if let Ok(content) = std::fs::read_to_string(entry.path()) {
-                        total += content.lines().count();
-                    }
-                }
-            }
+    fn branch(true_count: u64, false_count: u64) -> BranchInfo {
+        BranchInfo {
+            true_count,
+            false_count,
+            end_line: 0,
+            end_column: 0,
+            condition: None,
         }
-        total
+    }
+
+    #[test]
+    fn test_branch_edges() {
+        let mut data = CoverageData::default();
+        data.branches.insert(Location { column: 12, ..location("/p/a.c", 3) }, branch(0, 0));
+        data.branches.insert(Location { column: 7, ..location("/p/a.c", 3) }, branch(4, 0));
+        data.branches.insert(location("/p/a.c", 9), branch(1, 2));
+
+        let edges = data.branch_edges();
+        assert_eq!(edges.len(), 6);
+        assert_eq!((edges[0].location.column, edges[0].block, edges[0].arm), (7, 0, BranchArm::True));
+        assert_eq!((edges[1].count, edges[1].arm.id()), (0, 1));
+        assert_eq!((edges[2].block, edges[2].reached), (1, false));
+        assert_eq!((edges[4].location.line, edges[4].block), (9, 0));
+
+        assert_eq!(data.branch_arms(), (6, 3));
+        let summary = &data.file_summaries()[Path::new("/p/a.c")];
+        assert_eq!(summary.branch_coverage(), 50.0);
+        assert_eq!(CoverageData::default().branch_arms(), (0, 0));
+    }
+
+    fn lines(entries: &[(u32, u64)]) -> CoverageData {
+        let mut data = CoverageData::default();
+        for (line, count) in entries {
+            data.lines.insert(location("/p/a.c", *line), *count);
+        }
+        data
+    }
+
+    #[test]
+    fn test_set_operations() {
+        let mut fuzzer = lines(&[(1, 5), (2, 1), (3, 0), (4, 0)]);
+        fuzzer.branches.insert(location("/p/a.c", 2), branch(3, 0));
+        let mut tests = lines(&[(1, 2), (2, 0), (3, 4), (9, 1)]);
+        tests.branches.insert(location("/p/a.c", 2), branch(1, 1));
+
+        let union = fuzzer.union(&tests);
+        assert_eq!(union.line_count(Path::new("/p/a.c"), 1), Some(7));
+        assert_eq!((union.covered_lines(), union.total_lines()), (4, 5));
+        assert_eq!(union.branch_arms(), (2, 2));
+
+        let both = fuzzer.intersection(&tests);
+        assert_eq!((both.covered_lines(), both.total_lines()), (1, 3));
+        assert_eq!(both.branch_arms(), (2, 1));
+
+        let only_fuzzer = fuzzer.difference(&tests);
+        let lines: Vec<u32> = only_fuzzer.covered_line_locations().iter().map(|l| l.line).collect();
+        assert_eq!(lines, vec![2]);
+        assert_eq!(only_fuzzer.branch_arms(), (2, 0));
+
+        let only_tests = tests.difference(&fuzzer);
+        let lines: Vec<u32> = only_tests.covered_line_locations().iter().map(|l| l.line).collect();
+        assert_eq!(lines, vec![3, 9]);
+        assert_eq!(only_tests.branch_arms(), (2, 1));
+    }
+
+    #[test]
+    fn test_set_operations_mix_formats() -> io::Result<()> {
+        // The fuzzer's `llvm-cov export` JSON against the unit tests' LCOV, for
+        //  2   if (x > 5 && y)
+        let fuzzer = parse_llvm_export(
+            r#"{"data": [{"files": [{
+                "filename": "/p/a.c",
+                "segments": [[2, 3, 1, true, true, false], [3, 1, 0, false, false, false]],
+                "branches": [[2, 7, 2, 12, 3, 0, 0, 0, 4], [2, 16, 2, 17, 0, 3, 0, 0, 4]],
+                "mcdc_records": [[2, 7, 2, 17, 0, 0, 5, [false, true]]]
+            }], "functions": [
+                {"name": "f", "count": 3, "filenames": ["/p/a.c"],
+                 "regions": [[2, 3, 2, 18, 3, 0, 0, 0]], "branches": []}
+            ]}]}"#,
+        )?;
+        let tests = parse_lcov(
+            "SF:/p/a.c\nDA:2,1\nBRDA:2,0,0,1\nBRDA:2,0,1,0\nBRDA:2,0,2,1\nBRDA:2,0,3,0\n\
+             MCDC:2,2,t,1,0,x > 5\nMCDC:2,2,f,1,0,x > 5\nMCDC:2,2,t,0,1,y\nMCDC:2,2,f,0,1,y\n\
+             end_of_record\n",
+        )?;
+
+        let union = fuzzer.union(&tests);
+        assert_eq!(union.branches.len(), 2);
+        assert_eq!(union.branch_arms(), (4, 3));
+        assert_eq!(union.mcdc_conditions(), (2, 2));
+
+        let both = fuzzer.intersection(&tests);
+        assert_eq!(both.branch_arms(), (4, 1));
+        assert_eq!(both.mcdc_conditions(), (2, 0));
+        assert_eq!(both.summary().covered_regions, 1);
+
+        let only_fuzzer = fuzzer.difference(&tests);
+        assert_eq!(only_fuzzer.branch_arms(), (4, 1));
+        assert_eq!(only_fuzzer.mcdc_conditions(), (2, 1));
+        assert_eq!(only_fuzzer.summary().total_regions, 1);
+        assert_eq!(only_fuzzer.summary().covered_regions, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_detects_format() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let lcov = dir.path().join("tests.info");
+        std::fs::write(&lcov, "SF:/p/a.c\nDA:1,1\nend_of_record\n")?;
+        assert_eq!(CoverageData::load(&lcov)?.total_lines(), 1);
+
+        let json = dir.path().join("fuzz.json");
+        std::fs::write(&json, r#"{"data": [{"files": [], "functions": []}]}"#)?;
+        assert_eq!(CoverageData::load(&json)?.total_lines(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_branch_conditions() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let source = dir.path().join("a.c");
+        std::fs::write(&source, "int f(int x) {\n  if (x > 5 &&\n      x < 9)\n    return 1;\n")?;
+        let file = source.to_str().unwrap();
+
+        let condition = Location { column: 7, ..location(file, 2) };
+        let unreadable = Location { column: 7, ..location("/missing.c", 1) };
+        let mut data = CoverageData::default();
+        data.branches.insert(
+            condition.clone(),
+            BranchInfo { end_line: 3, end_column: 12, ..branch(1, 0) },
+        );
+        data.branches.insert(unreadable.clone(), branch(0, 0));
+        data.read_branch_conditions();
+
+        assert_eq!(data.branches[&condition].condition.as_deref(), Some("x > 5 && x < 9"));
+        assert_eq!(data.branches[&unreadable].condition, None);
+
+        // Cached sources are not read again
+        let mut sources = SourceLines::new();
+        data.branches.get_mut(&condition).unwrap().condition = None;
+        data.read_branch_conditions_cached(&mut sources);
+        std::fs::remove_file(&source)?;
+        data.branches.get_mut(&condition).unwrap().condition = None;
+        data.read_branch_conditions_cached(&mut sources);
+        assert_eq!(data.branches[&condition].condition.as_deref(), Some("x > 5 && x < 9"));
+
+        let lines: Vec<String> = vec!["  if (x > 5)".to_string()];
+        let start = Location { column: 7, ..location(file, 1) };
+        assert_eq!(source_span(&lines, &start, 1, 12), Some("x > 5".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_retain_files() {
+        let mut data = CoverageData::default();
+        data.lines.insert(location("/p/a.c", 1), 1);
+        data.lines.insert(location("/usr/include/stdio.h", 40), 0);
+
+        let filter = crate::FileFilter::default();
+        data.retain_files(|path| filter.matches(path));
+        assert_eq!(data.total_lines(), 1);
+        assert_eq!(data.covered_lines(), 1);
+    }
+
+    #[test]
+    fn test_process_coverage_errors() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut coverage = Coverage::new(crate::CoverageConfig {
+            output_dir: dir.path().to_path_buf(),
+            ..crate::CoverageConfig::default()
+        });
+        assert!(matches!(coverage.process_coverage(), Err(CompileError::InvalidConfig(_))));
+
+        let mut config = crate::CoverageConfig {
+            output_dir: dir.path().to_path_buf(),
+            binary: Some(dir.path().join("target")),
+            ..crate::CoverageConfig::default()
+        };
+        config.toolchain.llvm_profdata = PathBuf::from("no-such-llvm-profdata");
+        let mut coverage = Coverage::new(config);
+        assert!(matches!(coverage.process_coverage(),
+            Err(CompileError::Io(ref e)) if e.kind() == io::ErrorKind::NotFound));
+
+        std::fs::write(dir.path().join("default.profraw"), "")?;
+        assert!(matches!(coverage.process_coverage(),
+            Err(CompileError::LlvmToolNotFound { ref tool }) if tool == "no-such-llvm-profdata"));
+        Ok(())
     }
 }
@@ -0,0 +1,334 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Write as _};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::coverage::{by_condition, BranchArm, ConditionKey, CoverageData, Location};
+use super::coverage_report::escape_markup;
+use super::html_report::page_header;
+
+/// What changed in one file between two coverage snapshots
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileDiff {
+    pub newly_covered_lines: Vec<u32>,
+    pub newly_uncovered_lines: Vec<u32>,
+    pub newly_covered_branches: Vec<(Location, BranchArm)>,
+    pub newly_uncovered_branches: Vec<(Location, BranchArm)>,
+    pub newly_covered_functions: Vec<String>,
+    pub newly_uncovered_functions: Vec<String>,
+}
+
+impl FileDiff {
+    pub fn is_empty(&self) -> bool {
+        *self == FileDiff::default()
+    }
+}
+
+/// Coverage gained and lost between two snapshots, e.g. before and after changing
+/// the fuzzer configuration or the target. Code that only exists in one snapshot
+/// counts as uncovered in the other.
+#[derive(Debug, Clone, Default)]
+pub struct CoverageDiff {
+    /// Only files with changes
+    pub files: BTreeMap<PathBuf, FileDiff>,
+}
+
+impl CoverageDiff {
+    pub fn new(before: &CoverageData, after: &CoverageData) -> Self {
+        let mut files: BTreeMap<PathBuf, FileDiff> = BTreeMap::new();
+
+        let line_keys: BTreeSet<(&Path, u32)> = before
+            .lines
+            .keys()
+            .chain(after.lines.keys())
+            .map(|location| (location.file.as_path(), location.line))
+            .collect();
+        for (file, line) in line_keys {
+            let was = before.line_count(file, line).unwrap_or(0) > 0;
+            let is = after.line_count(file, line).unwrap_or(0) > 0;
+            if was != is {
+                let diff = files.entry(file.to_path_buf()).or_default();
+                if is {
+                    diff.newly_covered_lines.push(line);
+                } else {
+                    diff.newly_uncovered_lines.push(line);
+                }
+            }
+        }
+
+        // Matched by their order on the line, so LCOV and JSON snapshots compare
+        let before_branches = by_condition(&before.branches);
+        let after_branches = by_condition(&after.branches);
+        let branch_keys: BTreeSet<&ConditionKey> =
+            before_branches.keys().chain(after_branches.keys()).collect();
+        for key in branch_keys {
+            let was = before_branches.get(key);
+            let is = after_branches.get(key);
+            let Some((location, _)) = is.or(was) else {
+                continue;
+            };
+            for arm in BranchArm::ALL {
+                let was = was.is_some_and(|(_, info)| info.count(arm) > 0);
+                let is = is.is_some_and(|(_, info)| info.count(arm) > 0);
+                if was != is {
+                    let diff = files.entry(location.file.clone()).or_default();
+                    if is {
+                        diff.newly_covered_branches.push(((*location).clone(), arm));
+                    } else {
+                        diff.newly_uncovered_branches.push(((*location).clone(), arm));
+                    }
+                }
+            }
+        }
+
+        let names: BTreeSet<&String> = before.functions.keys().chain(after.functions.keys()).collect();
+        for name in names {
+            let was = before.functions.get(name).is_some_and(|f| f.called > 0);
+            let is = after.functions.get(name).is_some_and(|f| f.called > 0);
+            if was != is {
+                let Some(function) = after.functions.get(name).or_else(|| before.functions.get(name)) else {
+                    continue;
+                };
+                let diff = files.entry(function.file.clone()).or_default();
+                if is {
+                    diff.newly_covered_functions.push(name.clone());
+                } else {
+                    diff.newly_uncovered_functions.push(name.clone());
+                }
+            }
+        }
+
+        CoverageDiff { files }
+    }
+
+    /// Compare two coverage files, each LCOV or `llvm-cov export` JSON
+    pub fn load(before: &Path, after: &Path) -> io::Result<Self> {
+        Ok(CoverageDiff::new(&CoverageData::load(before)?, &CoverageData::load(after)?))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Total lines gained and lost
+    pub fn line_changes(&self) -> (usize, usize) {
+        self.files.values().fold((0, 0), |(gained, lost), diff| {
+            (gained + diff.newly_covered_lines.len(), lost + diff.newly_uncovered_lines.len())
+        })
+    }
+}
+
+impl fmt::Display for CoverageDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (gained, lost) = self.line_changes();
+        writeln!(f, "Coverage Diff: +{} / -{} lines", gained, lost)?;
+
+        for (file, diff) in &self.files {
+            writeln!(f, "{}:", file.display())?;
+            if !diff.newly_covered_lines.is_empty() {
+                writeln!(f, "  + lines {}", line_list(&diff.newly_covered_lines))?;
+            }
+            if !diff.newly_uncovered_lines.is_empty() {
+                writeln!(f, "  - lines {}", line_list(&diff.newly_uncovered_lines))?;
+            }
+            for (location, arm) in &diff.newly_covered_branches {
+                writeln!(f, "  + branch {}", branch_label(location, *arm))?;
+            }
+            for (location, arm) in &diff.newly_uncovered_branches {
+                writeln!(f, "  - branch {}", branch_label(location, *arm))?;
+            }
+            for name in &diff.newly_covered_functions {
+                writeln!(f, "  + function {}", name)?;
+            }
+            for name in &diff.newly_uncovered_functions {
+                writeln!(f, "  - function {}", name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Write the diff as a standalone HTML page
+pub fn generate_html_diff(diff: &CoverageDiff, output_path: &Path) -> io::Result<()> {
+    let (gained, lost) = diff.line_changes();
+    let mut html = page_header("Coverage Diff");
+    let _ = writeln!(
+        html,
+        "<h1>Coverage Diff</h1>\n<p><span class=\"arm hit\">+{} lines</span> <span class=\"arm miss\">-{} lines</span></p>",
+        gained, lost
+    );
+
+    html.push_str("<table>\n<tr><th>File</th><th>Kind</th><th>Newly covered</th><th>Newly uncovered</th></tr>\n");
+    for (file, diff) in &diff.files {
+        let branches = |edges: &[(Location, BranchArm)]| {
+            edges
+                .iter()
+                .map(|(location, arm)| branch_label(location, *arm))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let rows = [
+            ("Lines", line_list(&diff.newly_covered_lines), line_list(&diff.newly_uncovered_lines)),
+            ("Branches", branches(&diff.newly_covered_branches), branches(&diff.newly_uncovered_branches)),
+            ("Functions", diff.newly_covered_functions.join(", "), diff.newly_uncovered_functions.join(", ")),
+        ];
+        for (kind, covered, uncovered) in rows {
+            if covered.is_empty() && uncovered.is_empty() {
+                continue;
+            }
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td class=\"high\">{}</td><td class=\"low\">{}</td></tr>",
+                escape_markup(&file.display().to_string()),
+                kind,
+                escape_markup(&covered),
+                escape_markup(&uncovered)
+            );
+        }
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+
+    fs::write(output_path, html)
+}
+
+/// Collapse sorted line numbers into ranges, e.g. `3-5, 9`
+fn line_list(lines: &[u32]) -> String {
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+    for &line in lines {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == line => *end = line,
+            _ => ranges.push((line, line)),
+        }
+    }
+    ranges
+        .iter()
+        .map(|(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{}-{}", start, end)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn branch_label(location: &Location, arm: BranchArm) -> String {
+    let arm = match arm {
+        BranchArm::True => "true",
+        BranchArm::False => "false",
+    };
+    format!("{}:{} ({})", location.line, location.column, arm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BranchInfo, FunctionInfo};
+
+    fn snapshot(lines: &[(u32, u64)], branch: (u64, u64), called: u64) -> CoverageData {
+        let mut data = CoverageData::default();
+        for (line, count) in lines {
+            data.lines.insert(
+                Location {
+                    file: PathBuf::from("/p/a.c"),
+                    line: *line,
+                    column: 0,
+                },
+                *count,
+            );
+        }
+        data.branches.insert(
+            Location {
+                file: PathBuf::from("/p/a.c"),
+                line: 2,
+                column: 7,
+            },
+            BranchInfo {
+                true_count: branch.0,
+                false_count: branch.1,
+                end_line: 2,
+                end_column: 12,
+                condition: None,
+            },
+        );
+        data.functions.insert(
+            "helper".to_string(),
+            FunctionInfo {
+                name: "helper".to_string(),
+                file: PathBuf::from("/p/a.c"),
+                start_line: 10,
+                end_line: 12,
+                called,
+            },
+        );
+        data
+    }
+
+    #[test]
+    fn test_diff() {
+        let before = snapshot(&[(1, 1), (2, 1), (3, 0), (4, 0), (5, 0), (6, 2)], (1, 0), 0);
+        let after = snapshot(&[(1, 4), (2, 1), (3, 1), (4, 2), (5, 1), (6, 0)], (0, 3), 1);
+
+        let diff = CoverageDiff::new(&before, &after);
+        let file = &diff.files[Path::new("/p/a.c")];
+        assert_eq!(file.newly_covered_lines, vec![3, 4, 5]);
+        assert_eq!(file.newly_uncovered_lines, vec![6]);
+        assert_eq!(file.newly_covered_branches[0].1, BranchArm::False);
+        assert_eq!(file.newly_uncovered_branches[0].1, BranchArm::True);
+        assert_eq!(file.newly_covered_functions, vec!["helper".to_string()]);
+
+        let text = diff.to_string();
+        assert!(text.starts_with("Coverage Diff: +3 / -1 lines\n/p/a.c:\n  + lines 3-5\n  - lines 6\n"));
+        assert!(text.contains("  + branch 2:7 (false)\n"));
+
+        assert!(CoverageDiff::new(&after, &after).is_empty());
+    }
+
+    #[test]
+    fn test_diff_mix_formats() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let before = dir.path().join("old.lcov");
+        let after = dir.path().join("new.json");
+        fs::write(
+            &before,
+            "SF:/p/a.c\nDA:2,1\nBRDA:2,0,0,1\nBRDA:2,0,1,0\nBRDA:2,0,2,1\nBRDA:2,0,3,1\nend_of_record\n",
+        )?;
+        fs::write(
+            &after,
+            r#"{"data": [{"files": [{
+                "filename": "/p/a.c",
+                "segments": [[2, 3, 1, true, true, false], [3, 1, 0, false, false, false]],
+                "branches": [[2, 7, 2, 12, 1, 1, 0, 0, 4], [2, 16, 2, 17, 1, 0, 0, 0, 4]]
+            }], "functions": []}]}"#,
+        )?;
+
+        let diff = CoverageDiff::load(&before, &after)?;
+        let file = &diff.files[Path::new("/p/a.c")];
+        let column = |branches: &[(Location, BranchArm)]| -> Vec<(u32, BranchArm)> {
+            branches.iter().map(|(location, arm)| (location.column, *arm)).collect()
+        };
+        assert_eq!(column(&file.newly_covered_branches), vec![(7, BranchArm::False)]);
+        assert_eq!(column(&file.newly_uncovered_branches), vec![(16, BranchArm::False)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_html_diff() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let before = dir.path().join("before.lcov");
+        let after = dir.path().join("after.lcov");
+        fs::write(&before, "SF:/p/a&b.c\nDA:1,0\nDA:2,1\nend_of_record\n")?;
+        fs::write(&after, "SF:/p/a&b.c\nDA:1,3\nDA:2,1\nend_of_record\n")?;
+
+        let diff = CoverageDiff::load(&before, &after)?;
+        let path = dir.path().join("diff.html");
+        generate_html_diff(&diff, &path)?;
+
+        let html = fs::read_to_string(&path)?;
+        assert!(html.contains("+1 lines"));
+        assert!(html.contains("<td>/p/a&amp;b.c</td><td>Lines</td><td class=\"high\">1</td>"));
+        Ok(())
+    }
+}
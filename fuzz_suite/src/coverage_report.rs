@@ -1,9 +1,14 @@
-use std::collections::{HashMap,HashSet};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::fs::File;
 use std::io::{self, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
-use super::coverage::{CoverageData, Location};
+use super::coverage::{percentage, BranchArm, CoverageData, FileSummary, FunctionInfo, Location};
+
+/// Number of hottest lines listed in a report
+const HOT_SPOT_COUNT: usize = 10;
 
 #[derive(Debug)]
 pub struct CoverageReport {
@@ -12,32 +17,72 @@ pub struct CoverageReport {
     pub branch_coverage: f64,
     pub function_coverage: f64,
     pub line_coverage: f64,
+    /// Branch arms, two per condition, and how many were taken
+    pub total_branches: usize,
+    pub covered_branches: usize,
+    pub total_functions: usize,
+    pub covered_functions: usize,
+    /// Every instrumented function, ordered by file and line
+    pub functions: Vec<FunctionInfo>,
+    /// Conditions in MC/DC decisions, and how many had an independence pair covered
+    pub mcdc_conditions: usize,
+    pub mcdc_covered: usize,
+    pub mcdc_coverage: f64,
+    /// Most executed lines with their hit counts
+    pub hot_spots: Vec<(Location, u64)>,
 }
 
 impl CoverageReport {
-    pub fn new(coverage_data: &CoverageData, total_lines: usize) -> Self {
-        let covered_lines = coverage_data.lines.len();
+    pub fn new(coverage_data: &CoverageData) -> Self {
+        let total_lines = coverage_data.total_lines();
+        let covered_lines = coverage_data.covered_lines();
         let line_coverage = if total_lines > 0 {
             (covered_lines as f64 / total_lines as f64) * 100.0
         } else {
             0.0
         };
 
-        let branch_coverage = coverage_data.branches.values()
-            .filter(|info| info.taken)
-            .count() as f64 / coverage_data.branches.len() as f64 * 100.0;
+        let (total_branches, covered_branches) = coverage_data.branch_arms();
+        let branch_coverage = percentage(covered_branches, total_branches);
+
+        let mut functions: Vec<FunctionInfo> = coverage_data.functions.values().cloned().collect();
+        functions.sort_by(|a, b| {
+            (&a.file, a.start_line, &a.name).cmp(&(&b.file, b.start_line, &b.name))
+        });
+        let covered_functions = functions.iter().filter(|f| f.called > 0).count();
+        let function_coverage = percentage(covered_functions, functions.len());
+
+        let (mcdc_conditions, mcdc_covered) = coverage_data.mcdc_conditions();
+
+        let hot_spots = coverage_data
+            .hot_spots(HOT_SPOT_COUNT)
+            .into_iter()
+            .map(|(location, count)| (location.clone(), count))
+            .collect();
 
         CoverageReport {
             total_lines,
             covered_lines,
             branch_coverage,
-            function_coverage: 100.0, // Placeholder until we implement function tracking
+            function_coverage,
             line_coverage,
+            total_branches,
+            covered_branches,
+            total_functions: functions.len(),
+            covered_functions,
+            functions,
+            mcdc_conditions,
+            mcdc_covered,
+            mcdc_coverage: percentage(mcdc_covered, mcdc_conditions),
+            hot_spots,
         }
     }
+}
 
-    pub fn to_string(&self) -> String {
-        format!(
+impl fmt::Display for CoverageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
             "Coverage Report:\n\
              Total Lines: {}\n\
              Covered Lines: {}\n\
@@ -49,7 +94,43 @@ impl CoverageReport {
             self.line_coverage,
             self.branch_coverage,
             self.function_coverage
-        )
+        )?;
+
+        // Only binaries built with -fcoverage-mcdc have MC/DC data
+        if self.mcdc_conditions > 0 {
+            writeln!(
+                f,
+                "MC/DC Coverage: {:.2}% ({}/{} conditions)",
+                self.mcdc_coverage, self.mcdc_covered, self.mcdc_conditions
+            )?;
+        }
+
+        if !self.functions.is_empty() {
+            writeln!(
+                f,
+                "Functions ({}/{} called):",
+                self.covered_functions, self.total_functions
+            )?;
+            for function in &self.functions {
+                writeln!(
+                    f,
+                    "  {:>10}  {} ({}:{}-{})",
+                    function.called,
+                    function.name,
+                    function.file.display(),
+                    function.start_line,
+                    function.end_line
+                )?;
+            }
+        }
+
+        if !self.hot_spots.is_empty() {
+            writeln!(f, "Hot Spots:")?;
+            for (location, count) in &self.hot_spots {
+                writeln!(f, "  {:>10}  {}:{}", count, location.file.display(), location.line)?;
+            }
+        }
+        Ok(())
     }
 }
 
@@ -60,43 +141,100 @@ pub fn generate_lcov(
     let mut file = File::create(output_path)?;
     
     // Group coverage data by file
-    let mut file_coverage: HashMap<_, Vec<_>> = HashMap::new();
-    for location in &coverage_data.lines {
+    let mut file_coverage: BTreeMap<_, Vec<_>> = BTreeMap::new();
+    for (location, count) in &coverage_data.lines {
         file_coverage
             .entry(&location.file)
             .or_default()
-            .push(location);
+            .push((location.line, *count));
     }
 
-    for (file_path, locations) in file_coverage {
+    let branch_edges = coverage_data.branch_edges();
+
+    for (file_path, mut lines) in file_coverage {
         // Write file section
         writeln!(file, "SF:{}", file_path.display())?;
 
-        // Write function coverage (placeholder for now)
-        writeln!(file, "FNF:0")?;
-        writeln!(file, "FNH:0")?;
+        // Write function coverage
+        let mut functions: Vec<_> = coverage_data.functions
+            .values()
+            .filter(|function| function.file == *file_path)
+            .collect();
+        functions.sort_by_key(|function| (function.start_line, &function.name));
+        for function in &functions {
+            writeln!(file, "FN:{},{}", function.start_line, function.name)?;
+        }
+        for function in &functions {
+            writeln!(file, "FNDA:{},{}", function.called, function.name)?;
+        }
+        writeln!(file, "FNF:{}", functions.len())?;
+        writeln!(file, "FNH:{}", functions.iter().filter(|f| f.called > 0).count())?;
 
         // Write line coverage
-        for location in locations {
-            writeln!(file, "DA:{},1", location.line)?;
+        lines.sort_unstable();
+        for (line, count) in &lines {
+            writeln!(file, "DA:{},{}", line, count)?;
         }
+        writeln!(file, "LF:{}", lines.len())?;
+        writeln!(file, "LH:{}", lines.iter().filter(|(_, count)| *count > 0).count())?;
 
-        // Write branch coverage
-        let file_branches: Vec<_> = coverage_data.branches
+        // Write branch coverage, one record per arm. A taken count of `-` means the
+        // condition was never evaluated.
+        let edges: Vec<_> = branch_edges
             .iter()
-            .filter(|(loc, _)| loc.file == *file_path)
+            .filter(|edge| edge.location.file == *file_path)
             .collect();
-
-        for (location, info) in file_branches {
+        for edge in &edges {
+            let taken = if edge.reached {
+                edge.count.to_string()
+            } else {
+                "-".to_string()
+            };
             writeln!(
                 file,
                 "BRDA:{},{},{},{}",
-                location.line,
-                0, // branch number (placeholder)
-                0, // block number (placeholder)
-                if info.taken { info.count } else { 0 }
+                edge.location.line,
+                edge.block,
+                edge.arm.id(),
+                taken
             )?;
         }
+        writeln!(file, "BRF:{}", edges.len())?;
+        writeln!(file, "BRH:{}", edges.iter().filter(|edge| edge.count > 0).count())?;
+
+        // Write MC/DC coverage, one record per condition and outcome. The export only
+        // tells whether each condition has an independence pair, not which senses the
+        // test vectors demonstrated, so both senses get that same value.
+        let mut decisions: Vec<_> = coverage_data.mcdc
+            .iter()
+            .filter(|(loc, _)| loc.file == *file_path)
+            .collect();
+        decisions.sort_by_key(|(loc, _)| (loc.line, loc.column));
+
+        if !decisions.is_empty() {
+            let mut found = 0;
+            let mut hit = 0;
+            for (location, decision) in decisions {
+                for (index, covered) in decision.independence_pairs.iter().enumerate() {
+                    for sense in ["t", "f"] {
+                        writeln!(
+                            file,
+                            "MCDC:{},{},{},{},{},condition {}",
+                            location.line,
+                            decision.conditions(),
+                            sense,
+                            u8::from(*covered),
+                            index,
+                            index
+                        )?;
+                        found += 1;
+                        hit += usize::from(*covered);
+                    }
+                }
+            }
+            writeln!(file, "MCF:{}", found)?;
+            writeln!(file, "MCH:{}", hit)?;
+        }
 
         // Write end of record
         writeln!(file, "end_of_record")?;
@@ -105,28 +243,446 @@ pub fn generate_lcov(
     Ok(())
 }
 
-/// Format source code with coverage information
+/// Coverage of one line as the XML formats describe it
+#[derive(Debug, Default)]
+struct LineRecord {
+    hits: u64,
+    /// Branch arms starting on the line, and how many were taken
+    branches: usize,
+    covered_branches: usize,
+}
+
+/// Line records of every file, ordered by file and line
+fn line_records(coverage_data: &CoverageData) -> BTreeMap<PathBuf, BTreeMap<u32, LineRecord>> {
+    let mut files: BTreeMap<PathBuf, BTreeMap<u32, LineRecord>> = BTreeMap::new();
+    for (location, count) in &coverage_data.lines {
+        files
+            .entry(location.file.clone())
+            .or_default()
+            .entry(location.line)
+            .or_default()
+            .hits = *count;
+    }
+    for (location, info) in &coverage_data.branches {
+        let record = files
+            .entry(location.file.clone())
+            .or_default()
+            .entry(location.line)
+            .or_default();
+        record.branches += BranchArm::ALL.len();
+        record.covered_branches += BranchArm::ALL.iter().filter(|arm| info.count(**arm) > 0).count();
+    }
+    files
+}
+
+/// `part` of `total` as the 0-1 rate Cobertura uses
+fn rate(part: usize, total: usize) -> String {
+    format!("{:.4}", percentage(part, total) / 100.0)
+}
+
+/// Write Cobertura XML, with one package per source directory and one class per file
+pub fn generate_cobertura(
+    coverage_data: &CoverageData,
+    output_path: &Path,
+) -> io::Result<()> {
+    let mut file = File::create(output_path)?;
+    let records = line_records(coverage_data);
+    let summaries = coverage_data.file_summaries();
+
+    // Group files by the directory they live in
+    let mut packages: BTreeMap<PathBuf, Vec<&PathBuf>> = BTreeMap::new();
+    for path in records.keys() {
+        let package = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        packages.entry(package).or_default().push(path);
+    }
+
+    let total = coverage_data.summary();
+    let timestamp = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    writeln!(file, "<?xml version=\"1.0\" ?>")?;
+    writeln!(
+        file,
+        "<coverage line-rate=\"{}\" branch-rate=\"{}\" lines-covered=\"{}\" lines-valid=\"{}\" branches-covered=\"{}\" branches-valid=\"{}\" complexity=\"0\" version=\"0.1\" timestamp=\"{}\">",
+        rate(total.covered_lines, total.total_lines),
+        rate(total.covered_branches, total.total_branches),
+        total.covered_lines,
+        total.total_lines,
+        total.covered_branches,
+        total.total_branches,
+        timestamp
+    )?;
+    writeln!(file, "  <sources>\n    <source>.</source>\n  </sources>")?;
+    writeln!(file, "  <packages>")?;
+
+    for (package, paths) in &packages {
+        let mut package_total = FileSummary::default();
+        for path in paths {
+            if let Some(summary) = summaries.get(*path) {
+                package_total.add(summary);
+            }
+        }
+        let package_name = package.display().to_string();
+        writeln!(
+            file,
+            "    <package name=\"{}\" line-rate=\"{}\" branch-rate=\"{}\" complexity=\"0\">",
+            escape_markup(if package_name.is_empty() { "." } else { &package_name }),
+            rate(package_total.covered_lines, package_total.total_lines),
+            rate(package_total.covered_branches, package_total.total_branches)
+        )?;
+        writeln!(file, "      <classes>")?;
+
+        for path in paths {
+            let summary = summaries.get(*path).cloned().unwrap_or_default();
+            let lines = &records[*path];
+            let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+            writeln!(
+                file,
+                "        <class name=\"{}\" filename=\"{}\" line-rate=\"{}\" branch-rate=\"{}\" complexity=\"0\">",
+                escape_markup(&name),
+                escape_markup(&path.display().to_string()),
+                rate(summary.covered_lines, summary.total_lines),
+                rate(summary.covered_branches, summary.total_branches)
+            )?;
+
+            let mut functions: Vec<_> = coverage_data.functions
+                .values()
+                .filter(|function| function.file == **path)
+                .collect();
+            functions.sort_by_key(|function| (function.start_line, &function.name));
+            writeln!(file, "          <methods>")?;
+            for function in functions {
+                let body: Vec<_> = lines
+                    .range(function.start_line..=function.end_line)
+                    .collect();
+                let covered = body.iter().filter(|(_, record)| record.hits > 0).count();
+                writeln!(
+                    file,
+                    "            <method name=\"{}\" signature=\"\" line-rate=\"{}\" branch-rate=\"0\" complexity=\"0\">",
+                    escape_markup(&function.name),
+                    rate(covered, body.len())
+                )?;
+                writeln!(file, "              <lines>")?;
+                for (line, record) in body {
+                    writeln!(file, "                <line number=\"{}\" hits=\"{}\"/>", line, record.hits)?;
+                }
+                writeln!(file, "              </lines>\n            </method>")?;
+            }
+            writeln!(file, "          </methods>")?;
+
+            writeln!(file, "          <lines>")?;
+            for (line, record) in lines {
+                if record.branches > 0 {
+                    writeln!(
+                        file,
+                        "            <line number=\"{}\" hits=\"{}\" branch=\"true\" condition-coverage=\"{:.0}% ({}/{})\"/>",
+                        line,
+                        record.hits,
+                        percentage(record.covered_branches, record.branches),
+                        record.covered_branches,
+                        record.branches
+                    )?;
+                } else {
+                    writeln!(
+                        file,
+                        "            <line number=\"{}\" hits=\"{}\" branch=\"false\"/>",
+                        line, record.hits
+                    )?;
+                }
+            }
+            writeln!(file, "          </lines>\n        </class>")?;
+        }
+        writeln!(file, "      </classes>\n    </package>")?;
+    }
+
+    writeln!(file, "  </packages>\n</coverage>")?;
+    Ok(())
+}
+
+/// Write SonarQube's generic test coverage XML
+pub fn generate_sonarqube(
+    coverage_data: &CoverageData,
+    output_path: &Path,
+) -> io::Result<()> {
+    let mut file = File::create(output_path)?;
+
+    writeln!(file, "<coverage version=\"1\">")?;
+    for (path, lines) in line_records(coverage_data) {
+        writeln!(file, "  <file path=\"{}\">", escape_markup(&path.display().to_string()))?;
+        for (line, record) in lines {
+            write!(
+                file,
+                "    <lineToCover lineNumber=\"{}\" covered=\"{}\"",
+                line,
+                record.hits > 0
+            )?;
+            if record.branches > 0 {
+                write!(
+                    file,
+                    " branchesToCover=\"{}\" coveredBranches=\"{}\"",
+                    record.branches, record.covered_branches
+                )?;
+            }
+            writeln!(file, "/>")?;
+        }
+        writeln!(file, "  </file>")?;
+    }
+    writeln!(file, "</coverage>")?;
+    Ok(())
+}
+
+/// Escape text for use in XML and HTML content and attribute values
+pub(crate) fn escape_markup(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Format source code with coverage information, gcov style: each line is prefixed
+/// with its execution count, `#####` if it has code that never ran, or `-` if it has
+/// no code
 pub fn format_source_with_coverage(
     source: &str,
     coverage_data: &CoverageData,
     file_path: &Path,
 ) -> String {
     let mut result = String::new();
-    let covered_lines: HashSet<u32> = coverage_data.lines
+    let line_counts: HashMap<u32, u64> = coverage_data.lines
         .iter()
-        .filter(|loc| loc.file == file_path)
-        .map(|loc| loc.line)
+        .filter(|(loc, _)| loc.file == file_path)
+        .map(|(loc, count)| (loc.line, *count))
         .collect();
 
     for (line_num, line) in source.lines().enumerate() {
         let line_num = line_num as u32 + 1;
-        if covered_lines.contains(&line_num) {
-            result.push_str(" ");
-        } else {
-            result.push_str("# ");
-        }
-        result.push_str(&format!("{:2} {}\n", line_num, line));
+        let count = match line_counts.get(&line_num) {
+            Some(0) => "#####".to_string(),
+            Some(count) => count.to_string(),
+            None => "-".to_string(),
+        };
+        result.push_str(&format!("{:>9}:{:>5}: {}\n", count, line_num, line));
     }
 
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn sample_data() -> CoverageData {
+        let mut data = CoverageData::default();
+        for (line, count) in [(1, 3), (2, 0), (4, 12)] {
+            data.lines.insert(
+                Location {
+                    file: PathBuf::from("a.c"),
+                    line,
+                    column: 0,
+                },
+                count,
+            );
+        }
+        data
+    }
+
+    #[test]
+    fn test_report_counts_and_hot_spots() {
+        let report = CoverageReport::new(&sample_data());
+        assert_eq!(report.total_lines, 3);
+        assert_eq!(report.covered_lines, 2);
+        assert_eq!(report.hot_spots[0].0.line, 4);
+        assert_eq!(report.hot_spots[0].1, 12);
+        assert!(report.to_string().contains("Hot Spots:"));
+    }
+
+    #[test]
+    fn test_lcov_line_records() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("coverage.lcov");
+        generate_lcov(&sample_data(), &path)?;
+
+        let lcov = std::fs::read_to_string(&path)?;
+        assert!(lcov.contains("DA:1,3\nDA:2,0\nDA:4,12\nLF:3\nLH:2\n"));
+        Ok(())
+    }
+
+    fn add_function(data: &mut CoverageData, name: &str, start_line: u32, called: u64) {
+        data.functions.insert(
+            name.to_string(),
+            FunctionInfo {
+                name: name.to_string(),
+                file: PathBuf::from("a.c"),
+                start_line,
+                end_line: start_line + 2,
+                called,
+            },
+        );
+    }
+
+    #[test]
+    fn test_function_coverage() -> io::Result<()> {
+        let mut data = sample_data();
+        add_function(&mut data, "main", 1, 1);
+        add_function(&mut data, "unused", 4, 0);
+
+        let report = CoverageReport::new(&data);
+        assert_eq!(report.function_coverage, 50.0);
+        assert_eq!(report.functions[0].name, "main");
+        let text = report.to_string();
+        assert!(text.contains("Functions (1/2 called):"));
+        assert!(text.contains("unused (a.c:4-6)"));
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("coverage.lcov");
+        generate_lcov(&data, &path)?;
+        let lcov = std::fs::read_to_string(&path)?;
+        assert!(lcov.contains("FN:1,main\nFN:4,unused\nFNDA:1,main\nFNDA:0,unused\nFNF:2\nFNH:1\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_branch_coverage() -> io::Result<()> {
+        let report = CoverageReport::new(&sample_data());
+        assert_eq!(report.branch_coverage, 0.0);
+
+        let mut data = sample_data();
+        for (column, true_count, false_count) in [(5, 3, 0), (12, 0, 0)] {
+            data.branches.insert(
+                Location {
+                    file: PathBuf::from("a.c"),
+                    line: 2,
+                    column,
+                },
+                crate::BranchInfo {
+                    true_count,
+                    false_count,
+                    end_line: 2,
+                    end_column: column + 4,
+                    condition: None,
+                },
+            );
+        }
+
+        let report = CoverageReport::new(&data);
+        assert_eq!((report.covered_branches, report.total_branches), (1, 4));
+        assert_eq!(report.branch_coverage, 25.0);
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("coverage.lcov");
+        generate_lcov(&data, &path)?;
+        let lcov = std::fs::read_to_string(&path)?;
+        assert!(lcov.contains("BRDA:2,0,0,3\nBRDA:2,0,1,0\nBRDA:2,1,0,-\nBRDA:2,1,1,-\nBRF:4\nBRH:1\n"));
+        Ok(())
+    }
+
+    fn branch_data() -> CoverageData {
+        let mut data = sample_data();
+        data.lines.insert(
+            Location {
+                file: PathBuf::from("src/lib/b.c"),
+                line: 7,
+                column: 0,
+            },
+            1,
+        );
+        data.branches.insert(
+            Location {
+                file: PathBuf::from("a.c"),
+                line: 1,
+                column: 4,
+            },
+            crate::BranchInfo {
+                true_count: 3,
+                false_count: 0,
+                end_line: 1,
+                end_column: 9,
+                condition: None,
+            },
+        );
+        add_function(&mut data, "main", 1, 1);
+        data
+    }
+
+    #[test]
+    fn test_cobertura() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("cobertura.xml");
+        generate_cobertura(&branch_data(), &path)?;
+
+        let xml = std::fs::read_to_string(&path)?;
+        assert!(xml.contains("line-rate=\"0.7500\" branch-rate=\"0.5000\" lines-covered=\"3\" lines-valid=\"4\""));
+        assert!(xml.contains("<package name=\".\" line-rate=\"0.6667\""));
+        assert!(xml.contains("<package name=\"src/lib\" line-rate=\"1.0000\""));
+        assert!(xml.contains("<class name=\"b.c\" filename=\"src/lib/b.c\""));
+        assert!(xml.contains("<method name=\"main\" signature=\"\" line-rate=\"0.5000\""));
+        assert!(xml.contains("<line number=\"1\" hits=\"3\" branch=\"true\" condition-coverage=\"50% (1/2)\"/>"));
+        assert!(xml.contains("<line number=\"2\" hits=\"0\" branch=\"false\"/>"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sonarqube() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("sonar.xml");
+        generate_sonarqube(&branch_data(), &path)?;
+
+        let xml = std::fs::read_to_string(&path)?;
+        assert!(xml.starts_with("<coverage version=\"1\">\n  <file path=\"a.c\">\n"));
+        assert!(xml.contains("<lineToCover lineNumber=\"1\" covered=\"true\" branchesToCover=\"2\" coveredBranches=\"1\"/>"));
+        assert!(xml.contains("<lineToCover lineNumber=\"2\" covered=\"false\"/>"));
+        assert!(xml.contains("<file path=\"src/lib/b.c\">"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_mcdc_report_and_lcov() -> io::Result<()> {
+        let mut data = sample_data();
+        data.mcdc.insert(
+            Location {
+                file: PathBuf::from("a.c"),
+                line: 2,
+                column: 7,
+            },
+            crate::McdcInfo {
+                end_line: 2,
+                end_column: 20,
+                independence_pairs: vec![true, false],
+            },
+        );
+
+        let report = CoverageReport::new(&data);
+        assert_eq!(report.mcdc_coverage, 50.0);
+        assert!(report.to_string().contains("MC/DC Coverage: 50.00% (1/2 conditions)"));
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("coverage.lcov");
+        generate_lcov(&data, &path)?;
+        let lcov = std::fs::read_to_string(&path)?;
+        assert!(lcov.contains("MCDC:2,2,t,1,0,condition 0\n"));
+        assert!(lcov.contains("MCDC:2,2,f,0,1,condition 1\n"));
+        assert!(lcov.contains("MCF:4\nMCH:2\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_source_with_coverage() {
+        let formatted =
+            format_source_with_coverage("a\nb\nc\nd\n", &sample_data(), Path::new("a.c"));
+        let lines: Vec<&str> = formatted.lines().collect();
+        assert_eq!(lines[0], "        3:    1: a");
+        assert_eq!(lines[1], "    #####:    2: b");
+        assert_eq!(lines[2], "        -:    3: c");
+    }
+}
@@ -1,17 +1,297 @@
+use plotters::coord::ranged1d::{AsRangedCoord, ValueFormatter};
+use plotters::coord::Shift;
 use plotters::prelude::*;
+use std::error::Error;
 use std::path::Path;
+use std::time::Duration;
 
+use super::stats_log::read_stats;
+
+/// What the horizontal axis of a coverage plot measures
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XAxis {
+    Executions,
+    ElapsedTime,
+}
+
+/// Coverage at one point of a campaign
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CoveragePoint {
+    pub executions: u64,
+    pub elapsed: Duration,
+    /// Coverage in percent
+    pub coverage: f64,
+}
+
+impl CoveragePoint {
+    fn x(&self, axis: XAxis) -> f64 {
+        match axis {
+            XAxis::Executions => self.executions as f64,
+            XAxis::ElapsedTime => self.elapsed.as_secs_f64(),
+        }
+    }
+}
+
+/// One curve on a coverage plot, e.g. branch coverage or a whole campaign
+#[derive(Debug, Clone)]
+pub struct CoverageSeries {
+    pub name: String,
+    pub points: Vec<CoveragePoint>,
+}
+
+impl CoverageSeries {
+    pub fn new(name: &str) -> Self {
+        CoverageSeries {
+            name: name.to_string(),
+            points: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, executions: u64, elapsed: Duration, coverage: f64) {
+        self.points.push(CoveragePoint {
+            executions,
+            elapsed,
+            coverage,
+        });
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkerKind {
+    Crash,
+    NewSeed,
+}
+
+/// An event drawn as a vertical line across the plot
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlotMarker {
+    pub kind: MarkerKind,
+    pub executions: u64,
+    pub elapsed: Duration,
+}
+
+impl PlotMarker {
+    fn x(&self, axis: XAxis) -> f64 {
+        match axis {
+            XAxis::Executions => self.executions as f64,
+            XAxis::ElapsedTime => self.elapsed.as_secs_f64(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PlotOptions {
+    pub title: String,
+    pub x_axis: XAxis,
+    /// Logarithmic x axis, useful for long campaigns where most progress is early
+    pub log_scale: bool,
+    pub markers: Vec<PlotMarker>,
+    pub size: (u32, u32),
+}
+
+impl Default for PlotOptions {
+    fn default() -> Self {
+        PlotOptions {
+            title: "Code Coverage Over Time".to_string(),
+            x_axis: XAxis::Executions,
+            log_scale: false,
+            markers: Vec::new(),
+            size: (800, 600),
+        }
+    }
+}
+
+impl PlotOptions {
+    pub fn with_title(mut self, title: &str) -> Self {
+        self.title = title.to_string();
+        self
+    }
+
+    pub fn with_x_axis(mut self, x_axis: XAxis) -> Self {
+        self.x_axis = x_axis;
+        self
+    }
+
+    pub fn with_log_scale(mut self, log_scale: bool) -> Self {
+        self.log_scale = log_scale;
+        self
+    }
+
+    pub fn with_markers(mut self, markers: Vec<PlotMarker>) -> Self {
+        self.markers.extend(markers);
+        self
+    }
+}
+
+/// Plot several coverage curves on one chart. The format follows the extension of
+/// `output_path`: SVG for `.svg`, PNG (or any other bitmap format) otherwise.
+pub fn plot_series(
+    series: &[CoverageSeries],
+    options: &PlotOptions,
+    output_path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    if output_path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("svg")) {
+        let root = SVGBackend::new(output_path, options.size).into_drawing_area();
+        draw_series(&root, series, options)
+    } else {
+        let root = BitMapBackend::new(output_path, options.size).into_drawing_area();
+        draw_series(&root, series, options)
+    }
+}
+
+/// Plot line, branch and function coverage from a campaign's `fuzzer_stats` file
+pub fn plot_stats(
+    stats_path: &Path,
+    options: &PlotOptions,
+    output_path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let records = read_stats(stats_path)?;
+    let mut lines = CoverageSeries::new("Lines");
+    let mut branches = CoverageSeries::new("Branches");
+    let mut functions = CoverageSeries::new("Functions");
+    for record in &records {
+        let elapsed = Duration::from_secs_f64(record.elapsed_secs);
+        lines.push(record.executions, elapsed, record.line_coverage);
+        branches.push(record.executions, elapsed, record.branch_coverage);
+        functions.push(record.executions, elapsed, record.function_coverage);
+    }
+    plot_series(&[lines, branches, functions], options, output_path)
+}
+
+fn draw_series<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    series: &[CoverageSeries],
+    options: &PlotOptions,
+) -> Result<(), Box<dyn Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let axis = options.x_axis;
+    let xs = series
+        .iter()
+        .flat_map(|s| s.points.iter().map(|p| p.x(axis)))
+        .chain(options.markers.iter().map(|m| m.x(axis)));
+    let max_x = xs.fold(0.0, f64::max);
+    let max_y = series
+        .iter()
+        .flat_map(|s| s.points.iter().map(|p| p.coverage))
+        .fold(0.0, f64::max);
+    // Leave some headroom above the highest curve, but never beyond 100%
+    let max_y = (max_y * 1.1).clamp(1.0, 100.0);
+
+    if options.log_scale {
+        // Log axes can't start at zero; the first execution or millisecond is the origin
+        let min_x = match axis {
+            XAxis::Executions => 1.0,
+            XAxis::ElapsedTime => 0.001,
+        };
+        draw_chart(root, series, options, (min_x..max_x.max(min_x * 10.0)).log_scale(), min_x, max_y)
+    } else {
+        draw_chart(root, series, options, 0.0..max_x.max(1.0), 0.0, max_y)
+    }
+}
+
+fn draw_chart<DB, X>(
+    root: &DrawingArea<DB, Shift>,
+    series: &[CoverageSeries],
+    options: &PlotOptions,
+    x_range: X,
+    min_x: f64,
+    max_y: f64,
+) -> Result<(), Box<dyn Error>>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+    X: AsRangedCoord<Value = f64>,
+    X::CoordDescType: ValueFormatter<f64>,
+{
+    root.fill(&WHITE)?;
+    let axis = options.x_axis;
+
+    let mut chart = ChartBuilder::on(root)
+        .caption(&options.title, ("sans-serif", 30).into_font())
+        .margin(10)
+        .x_label_area_size(40)
+        .y_label_area_size(50)
+        .build_cartesian_2d(x_range, 0.0..max_y)?;
+
+    chart
+        .configure_mesh()
+        .x_desc(match axis {
+            XAxis::Executions => "Executions",
+            XAxis::ElapsedTime => "Elapsed Time (s)",
+        })
+        .y_desc("Coverage (%)")
+        .draw()?;
+
+    for marker in &options.markers {
+        let color = match marker.kind {
+            MarkerKind::Crash => RED.mix(0.6),
+            MarkerKind::NewSeed => GREEN.mix(0.3),
+        };
+        let x = marker.x(axis).max(min_x);
+        chart.draw_series(LineSeries::new([(x, 0.0), (x, max_y)], color))?;
+    }
+
+    for (index, curve) in series.iter().enumerate() {
+        let color = Palette99::pick(index).to_rgba();
+        chart
+            .draw_series(LineSeries::new(
+                curve.points.iter().map(|p| (p.x(axis).max(min_x), p.coverage)),
+                color.stroke_width(2),
+            ))?
+            .label(curve.name.as_str())
+            .legend(move |(x, y)| PathElement::new([(x, y), (x + 20, y)], color.stroke_width(2)));
+    }
+
+    if series.iter().any(|curve| !curve.name.is_empty()) {
+        chart
+            .configure_series_labels()
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .position(SeriesLabelPosition::LowerRight)
+            .draw()?;
+    }
+
+    root.present()?;
+    Ok(())
+}
+
+/// Plot a campaign's `fuzzer_stats` file against executions: line, branch and function
+/// coverage, as PNG or SVG depending on the extension. Use `plot_stats` to choose the
+/// axis, scale or title.
 pub fn plot_coverage(
-    coverage_data: &[f64],
+    stats_path: &Path,
     output_path: &Path,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let root = BitMapBackend::new(output_path, (800, 600)).into_drawing_area();
+    plot_stats(stats_path, &PlotOptions::default(), output_path)
+}
+
+/// The coverage-over-time chart as an SVG document, for embedding in HTML reports
+pub fn coverage_chart_svg(coverage_data: &[f64]) -> Result<String, Box<dyn Error>> {
+    let mut svg = String::new();
+    {
+        let root = SVGBackend::with_string(&mut svg, (800, 400)).into_drawing_area();
+        draw_coverage(&root, coverage_data)?;
+    }
+    Ok(svg)
+}
+
+fn draw_coverage<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    coverage_data: &[f64],
+) -> Result<(), Box<dyn Error>>
+where
+    DB::ErrorType: 'static,
+{
     root.fill(&WHITE)?;
 
-    let max_coverage = coverage_data.iter().cloned().fold(0./0., f64::max);
+    // At least 1%, so that a history of zeros, before anything is covered, still has
+    // a range to draw in
+    let max_coverage = coverage_data.iter().cloned().fold(0.0, f64::max).clamp(1.0, 100.0);
     let min_coverage = 0.0;
 
-    let mut chart = ChartBuilder::on(&root)
+    let mut chart = ChartBuilder::on(root)
         .caption("Code Coverage Over Time", ("sans-serif", 30).into_font())
         .margin(10)
         .x_label_area_size(30)
@@ -70,3 +350,106 @@ pub fn plot_cumulative_coverage(
     root.present()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn series() -> Vec<CoverageSeries> {
+        let mut lines = CoverageSeries::new("Lines");
+        let mut branches = CoverageSeries::new("Branches");
+        for i in 1..=50u64 {
+            let elapsed = Duration::from_millis(i * 20);
+            lines.push(i, elapsed, (i as f64).ln() * 10.0);
+            branches.push(i, elapsed, (i as f64).ln() * 6.0);
+        }
+        vec![lines, branches]
+    }
+
+    #[test]
+    fn test_plot_series_svg() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("coverage.svg");
+        let options = PlotOptions::default()
+            .with_x_axis(XAxis::ElapsedTime)
+            .with_log_scale(true)
+            .with_markers(vec![PlotMarker {
+                kind: MarkerKind::Crash,
+                executions: 10,
+                elapsed: Duration::from_millis(200),
+            }]);
+        plot_series(&series(), &options, &path)?;
+
+        let svg = std::fs::read_to_string(&path)?;
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("Branches"));
+        assert!(svg.contains("Elapsed Time (s)"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_plot_stats() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let mut log = crate::StatsLog::new(dir.path());
+        let mut stats = crate::CampaignStats::default();
+        for executions in [10, 100, 1000] {
+            stats.executions = executions;
+            stats.coverage.total_lines = 100;
+            stats.coverage.covered_lines = executions as usize / 20;
+            log.write(&stats)?;
+        }
+
+        let path = dir.path().join("stats.svg");
+        plot_stats(log.path(), &PlotOptions::default().with_log_scale(true), &path)?;
+        assert!(std::fs::read_to_string(&path)?.contains("Functions"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_plot_coverage_from_stats_file() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let stats_path = dir.path().join(crate::STATS_FILE);
+        let record = |executions: u64, line_coverage: f64| {
+            format!(
+                "{{\"timestamp\": 1700000000, \"elapsed_secs\": {}, \"executions\": {}, \"execs_per_sec\": 10.0, \
+                 \"line_coverage\": {}, \"branch_coverage\": 0.0, \"function_coverage\": 50.0, \
+                 \"covered_lines\": 0, \"total_lines\": 0, \"covered_branches\": 0, \"total_branches\": 0, \
+                 \"corpus_size\": 1, \"unique_crashes\": 0, \"hangs\": 0}}\n",
+                executions as f64 / 10.0,
+                executions,
+                line_coverage
+            )
+        };
+        std::fs::write(&stats_path, record(10, 20.0) + &record(100, 45.5))?;
+
+        let path = dir.path().join("coverage.svg");
+        plot_coverage(&stats_path, &path)?;
+        let svg = std::fs::read_to_string(&path)?;
+        assert!(svg.contains("Lines"));
+        assert!(svg.contains("Executions"));
+
+        let png = dir.path().join("coverage.png");
+        plot_coverage(&stats_path, &png)?;
+        assert!(std::fs::metadata(&png)?.len() > 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_coverage_chart_all_zero() -> Result<(), Box<dyn Error>> {
+        let svg = coverage_chart_svg(&[0.0, 0.0, 0.0])?;
+        assert!(svg.contains("Coverage (%)"));
+        // The y axis still gets a scale to draw the flat line against
+        assert!(svg.split("</text>").any(|text| text.trim_end().ends_with("1.0")));
+        assert!(!svg.contains("NaN"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_plot_series_png() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("coverage.png");
+        plot_series(&series(), &PlotOptions::default(), &path)?;
+        assert!(std::fs::metadata(&path)?.len() > 0);
+        Ok(())
+    }
+}
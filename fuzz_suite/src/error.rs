@@ -0,0 +1,231 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+
+use thiserror::Error;
+
+use super::sanitizer::Sanitizer;
+
+/// Errors from compiling targets and invoking the LLVM toolchain
+#[derive(Debug, Error)]
+pub enum CompileError {
+    #[error("compiler `{compiler}` not found")]
+    CompilerNotFound { compiler: String },
+
+    #[error("compilation of {} failed ({status}):\n{stderr}", input.display())]
+    CompilationFailed {
+        input: PathBuf,
+        status: ExitStatus,
+        stderr: String,
+        diagnostics: Vec<Diagnostic>,
+    },
+
+    #[error("build command `{command}` failed ({status})")]
+    BuildCommandFailed { command: String, status: ExitStatus },
+
+    #[error("LLVM tool `{tool}` not found")]
+    LlvmToolNotFound { tool: String },
+
+    #[error("`{tool}` failed: {message}")]
+    LlvmToolFailed { tool: String, message: String },
+
+    #[error("`{tool}` is LLVM {found} but `{first}` is LLVM {expected}")]
+    ToolchainMismatch {
+        first: String,
+        expected: u32,
+        tool: String,
+        found: u32,
+    },
+
+    #[error("-fsanitize={} cannot be combined with -fsanitize={}", first.flag_name(), second.flag_name())]
+    IncompatibleSanitizers { first: Sanitizer, second: Sanitizer },
+
+    #[error("invalid build configuration: {0}")]
+    InvalidConfig(String),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl CompileError {
+    /// Diagnostics the compiler reported, if this is a compilation failure
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        match self {
+            CompileError::CompilationFailed { diagnostics, .. } => diagnostics,
+            _ => &[],
+        }
+    }
+
+    /// Turn a failed spawn of `compiler` into `CompilerNotFound` where that's the cause
+    pub(crate) fn from_spawn(compiler: &str, err: io::Error) -> Self {
+        if err.kind() == io::ErrorKind::NotFound {
+            CompileError::CompilerNotFound {
+                compiler: compiler.to_string(),
+            }
+        } else {
+            CompileError::Io(err)
+        }
+    }
+
+    /// Turn a failed spawn of an LLVM tool into `LlvmToolNotFound` where that's the cause
+    pub(crate) fn from_tool_spawn(tool: &str, err: io::Error) -> Self {
+        if err.kind() == io::ErrorKind::NotFound {
+            CompileError::LlvmToolNotFound {
+                tool: tool.to_string(),
+            }
+        } else {
+            CompileError::Io(err)
+        }
+    }
+}
+
+/// For callers that only deal in I/O errors
+impl From<CompileError> for io::Error {
+    fn from(err: CompileError) -> Self {
+        match err {
+            CompileError::Io(err) => err,
+            err => io::Error::other(err),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Note,
+    Warning,
+    Error,
+    Fatal,
+}
+
+impl Severity {
+    fn label(&self) -> &'static str {
+        match self {
+            Severity::Note => "note",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+            Severity::Fatal => "fatal error",
+        }
+    }
+}
+
+/// One message from the compiler, e.g. `main.c:12:5: error: use of undeclared identifier`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// Missing for driver messages such as `clang: error: no input files`
+    pub file: Option<PathBuf>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file.display())?;
+            if let Some(line) = self.line {
+                write!(f, "{}:", line)?;
+            }
+            if let Some(column) = self.column {
+                write!(f, "{}:", column)?;
+            }
+            write!(f, " ")?;
+        }
+        write!(f, "{}: {}", self.severity.label(), self.message)
+    }
+}
+
+/// Extract the diagnostics from clang's stderr, ignoring source excerpts and carets
+pub fn parse_diagnostics(stderr: &str) -> Vec<Diagnostic> {
+    const MARKERS: &[(&str, Severity)] = &[
+        (": fatal error: ", Severity::Fatal),
+        (": error: ", Severity::Error),
+        (": warning: ", Severity::Warning),
+        (": note: ", Severity::Note),
+    ];
+
+    stderr
+        .lines()
+        .filter_map(|line| {
+            let (idx, marker, severity) = MARKERS
+                .iter()
+                .filter_map(|(marker, severity)| line.find(marker).map(|idx| (idx, *marker, *severity)))
+                .min_by_key(|(idx, _, _)| *idx)?;
+            let prefix = &line[..idx];
+            let message = line[idx + marker.len()..].to_string();
+            let (file, line, column) = parse_location(prefix);
+            Some(Diagnostic {
+                file,
+                line,
+                column,
+                severity,
+                message,
+            })
+        })
+        .collect()
+}
+
+/// Split `file:line:col`, `file:line` or a bare driver name such as `clang`
+fn parse_location(prefix: &str) -> (Option<PathBuf>, Option<u32>, Option<u32>) {
+    let mut parts = prefix.rsplitn(3, ':');
+    let last = parts.next().and_then(|p| p.parse::<u32>().ok());
+    let middle = parts.next();
+    let rest = parts.next();
+
+    match (last, middle, rest) {
+        (Some(column), Some(line), Some(file)) if line.parse::<u32>().is_ok() => (
+            Some(PathBuf::from(file)),
+            line.parse().ok(),
+            Some(column),
+        ),
+        (Some(line), Some(_), _) => {
+            let file = prefix.rsplit_once(':').map(|(file, _)| PathBuf::from(file));
+            (file, Some(line), None)
+        }
+        _ => (None, None, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_diagnostics() {
+        let stderr = "\
+src/main.c:12:5: error: use of undeclared identifier 'x'
+    x = 1;
+    ^
+src/util.h:3:10: warning: unused variable 'y' [-Wunused-variable]
+src/util.h:7: note: previous definition is here
+clang: error: linker command failed with exit code 1 (use -v to see invocation)
+2 errors generated.
+";
+        let diagnostics = parse_diagnostics(stderr);
+        assert_eq!(diagnostics.len(), 4);
+
+        assert_eq!(diagnostics[0].file, Some(PathBuf::from("src/main.c")));
+        assert_eq!(diagnostics[0].line, Some(12));
+        assert_eq!(diagnostics[0].column, Some(5));
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[0].message, "use of undeclared identifier 'x'");
+
+        assert_eq!(diagnostics[1].severity, Severity::Warning);
+        assert_eq!(diagnostics[2].line, Some(7));
+        assert_eq!(diagnostics[2].column, None);
+
+        assert_eq!(diagnostics[3].file, None);
+        assert_eq!(diagnostics[3].severity, Severity::Error);
+    }
+
+    #[test]
+    fn test_fatal_error_and_display() {
+        let diagnostics = parse_diagnostics("a.c:1:10: fatal error: 'missing.h' file not found");
+        assert_eq!(diagnostics[0].severity, Severity::Fatal);
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "a.c:1:10: fatal error: 'missing.h' file not found"
+        );
+    }
+}
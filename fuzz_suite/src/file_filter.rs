@@ -0,0 +1,123 @@
+use std::io;
+use std::path::Path;
+
+use glob::Pattern;
+
+/// Directories holding compiler and OS headers, never part of the target's own code
+const SYSTEM_PREFIXES: &[&str] = &[
+    "/usr/include",
+    "/usr/lib",
+    "/usr/local/include",
+    "/usr/local/lib",
+    "/usr/share",
+    "/Library/Developer",
+    "/Applications/Xcode.app",
+];
+
+/// Decides which source files count towards coverage totals.
+///
+/// Globs are matched against the full path of each file as reported by LLVM, so use a
+/// leading `**/` to match anywhere, e.g. `**/third_party/**`.
+#[derive(Debug, Clone)]
+pub struct FileFilter {
+    /// If non-empty, only files matching one of these globs count
+    pub include: Vec<String>,
+    /// Files matching any of these globs never count
+    pub exclude: Vec<String>,
+    pub exclude_system_headers: bool,
+}
+
+impl Default for FileFilter {
+    fn default() -> Self {
+        FileFilter {
+            include: vec![],
+            exclude: vec![],
+            exclude_system_headers: true,
+        }
+    }
+}
+
+impl FileFilter {
+    pub fn with_include(mut self, globs: Vec<String>) -> Self {
+        self.include.extend(globs);
+        self
+    }
+
+    pub fn with_exclude(mut self, globs: Vec<String>) -> Self {
+        self.exclude.extend(globs);
+        self
+    }
+
+    /// Check that every glob parses
+    pub fn validate(&self) -> io::Result<()> {
+        for glob in self.include.iter().chain(&self.exclude) {
+            Pattern::new(glob).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Invalid file glob {:?}: {}", glob, e),
+                )
+            })?;
+        }
+        Ok(())
+    }
+
+    pub fn matches(&self, path: &Path) -> bool {
+        if self.exclude_system_headers && is_system_path(path) {
+            return false;
+        }
+        if matches_any(&self.exclude, path) {
+            return false;
+        }
+        self.include.is_empty() || matches_any(&self.include, path)
+    }
+}
+
+/// Globs that don't parse match nothing; `validate` reports them
+fn matches_any(globs: &[String], path: &Path) -> bool {
+    globs
+        .iter()
+        .filter_map(|glob| Pattern::new(glob).ok())
+        .any(|pattern| pattern.matches_path(path))
+}
+
+fn is_system_path(path: &Path) -> bool {
+    SYSTEM_PREFIXES.iter().any(|prefix| path.starts_with(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_system_headers_excluded_by_default() {
+        let filter = FileFilter::default();
+        assert!(filter.matches(Path::new("/home/me/proj/src/main.c")));
+        assert!(!filter.matches(Path::new("/usr/include/stdio.h")));
+        assert!(!filter.matches(Path::new("/usr/lib/llvm-17/include/c++/v1/vector")));
+
+        let filter = FileFilter {
+            exclude_system_headers: false,
+            ..FileFilter::default()
+        };
+        assert!(filter.matches(Path::new("/usr/include/stdio.h")));
+    }
+
+    #[test]
+    fn test_include_and_exclude_globs() {
+        let filter = FileFilter::default()
+            .with_include(vec!["**/src/**".to_string()])
+            .with_exclude(vec!["**/src/vendor/**".to_string(), "**/*_test.c".to_string()]);
+
+        assert!(filter.matches(Path::new("/proj/src/parser/lexer.c")));
+        assert!(!filter.matches(Path::new("/proj/tools/gen.c")));
+        assert!(!filter.matches(Path::new("/proj/src/vendor/zlib/inflate.c")));
+        assert!(!filter.matches(Path::new("/proj/src/lexer_test.c")));
+    }
+
+    #[test]
+    fn test_invalid_glob() {
+        let filter = FileFilter::default().with_exclude(vec!["[".to_string()]);
+        assert!(filter.validate().is_err());
+        assert!(FileFilter::default().validate().is_ok());
+    }
+}
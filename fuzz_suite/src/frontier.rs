@@ -0,0 +1,274 @@
+use std::fmt;
+
+use super::coverage::{BranchArm, BranchInfo, CoverageData, Location, RegionInfo};
+
+/// A branch the fuzzer has reached but only ever sent one way
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrontierBranch {
+    pub location: Location,
+    pub untaken: BranchArm,
+    /// How often the other arm was taken
+    pub taken_count: u64,
+    /// Source text of the condition, if it could be read
+    pub condition: Option<String>,
+    /// Function containing the branch
+    pub function: Option<String>,
+    /// Never-executed lines behind the untaken arm, bounded by the arm's coverage region
+    /// where the data has regions, within the same function
+    pub blocked_lines: Vec<u32>,
+}
+
+/// Branches with exactly one arm taken, the comparisons the fuzzer can't solve yet.
+/// The ones guarding the most uncovered code come first; these are where a dictionary
+/// entry or grammar rule pays off most.
+#[derive(Debug, Clone, Default)]
+pub struct FrontierReport {
+    pub branches: Vec<FrontierBranch>,
+}
+
+impl FrontierReport {
+    pub fn new(coverage_data: &CoverageData) -> Self {
+        let mut branches: Vec<FrontierBranch> = coverage_data
+            .branches
+            .iter()
+            .filter_map(|(location, info)| {
+                let (untaken, taken_count) = match (info.true_count, info.false_count) {
+                    (0, taken) if taken > 0 => (BranchArm::True, taken),
+                    (taken, 0) if taken > 0 => (BranchArm::False, taken),
+                    _ => return None,
+                };
+                let function = coverage_data.functions.values().find(|function| {
+                    function.file == location.file
+                        && (function.start_line..=function.end_line).contains(&location.line)
+                });
+                let last_line = function.map_or(u32::MAX, |function| function.end_line);
+
+                Some(FrontierBranch {
+                    location: location.clone(),
+                    untaken,
+                    taken_count,
+                    condition: info.condition.clone(),
+                    function: function.map(|function| function.name.clone()),
+                    blocked_lines: blocked_lines(coverage_data, location, info, untaken, last_line),
+                })
+            })
+            .collect();
+
+        branches.sort_by(|a, b| {
+            b.blocked_lines
+                .len()
+                .cmp(&a.blocked_lines.len())
+                .then_with(|| b.taken_count.cmp(&a.taken_count))
+                .then_with(|| {
+                    (&a.location.file, a.location.line, a.location.column)
+                        .cmp(&(&b.location.file, b.location.line, b.location.column))
+                })
+        });
+        FrontierReport { branches }
+    }
+}
+
+/// Lines behind the untaken arm. With region data from `llvm-cov export`, these are the
+/// never-executed lines of the region the arm leads to: the region right after the
+/// condition for the true arm, and the one after that for the false arm (an `else`, or
+/// the code following an `if` whose body never falls through). LCOV has no regions, so
+/// there it is the first run of never-executed lines after the condition.
+fn blocked_lines(
+    coverage_data: &CoverageData,
+    location: &Location,
+    info: &BranchInfo,
+    untaken: BranchArm,
+    last_line: u32,
+) -> Vec<u32> {
+    let regions: Vec<&RegionInfo> = coverage_data
+        .regions
+        .iter()
+        .filter(|region| region.start.file == location.file)
+        .collect();
+    if regions.is_empty() {
+        // Lines without code don't break the run
+        return line_counts(coverage_data, location, info.end_line.saturating_add(1), last_line)
+            .into_iter()
+            .skip_while(|(_, count)| *count > 0)
+            .take_while(|(_, count)| *count == 0)
+            .map(|(line, _)| line)
+            .collect();
+    }
+
+    // First region starting at or after a position
+    let next_region = |line: u32, column: u32| {
+        regions
+            .iter()
+            .filter(|region| (region.start.line, region.start.column) >= (line, column))
+            .min_by_key(|region| (region.start.line, region.start.column))
+            .copied()
+    };
+    let Some(then_region) = next_region(info.end_line, info.end_column) else {
+        return vec![];
+    };
+    let arm_region = match untaken {
+        BranchArm::True => Some(then_region),
+        BranchArm::False => next_region(then_region.end_line, then_region.end_column),
+    };
+    match arm_region {
+        Some(region) if region.count == 0 => {
+            line_counts(coverage_data, location, region.start.line, region.end_line.min(last_line))
+                .into_iter()
+                .filter(|(_, count)| *count == 0)
+                .map(|(line, _)| line)
+                .collect()
+        }
+        _ => vec![],
+    }
+}
+
+/// Execution counts of the lines from `first` to `last` in the branch's file, in order
+fn line_counts(coverage_data: &CoverageData, location: &Location, first: u32, last: u32) -> Vec<(u32, u64)> {
+    let mut lines: Vec<(u32, u64)> = coverage_data
+        .lines
+        .iter()
+        .filter(|(line, _)| line.file == location.file && (first..=last).contains(&line.line))
+        .map(|(line, count)| (line.line, *count))
+        .collect();
+    lines.sort_unstable();
+    lines
+}
+
+impl fmt::Display for FrontierReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Coverage Frontier ({} half-covered branches):", self.branches.len())?;
+        for branch in &self.branches {
+            let (untaken, taken) = match branch.untaken {
+                BranchArm::True => ("true", "false"),
+                BranchArm::False => ("false", "true"),
+            };
+            write!(
+                f,
+                "  {:>5} lines  {}:{}:{}  {} arm never taken",
+                branch.blocked_lines.len(),
+                branch.location.file.display(),
+                branch.location.line,
+                branch.location.column,
+                untaken
+            )?;
+            if let Some(condition) = &branch.condition {
+                write!(f, " for `{}`", condition)?;
+            }
+            write!(f, " ({} taken {} times", taken, branch.taken_count)?;
+            if let Some(function) = &branch.function {
+                write!(f, ", in {}", function)?;
+            }
+            writeln!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FunctionInfo, RegionInfo};
+    use std::path::PathBuf;
+
+    fn location(line: u32, column: u32) -> Location {
+        Location {
+            file: PathBuf::from("/src/parse.c"),
+            line,
+            column,
+        }
+    }
+
+    #[test]
+    fn test_frontier_ranking() {
+        let mut data = CoverageData::default();
+        // 2: if (magic == 0xCAFE) { 3-6 never run }  9: if (len > 0) { 10 never run }
+        for (line, count) in [(1, 8), (2, 8), (3, 0), (4, 0), (6, 0), (7, 8), (9, 8), (10, 0), (11, 8), (14, 0)] {
+            data.lines.insert(location(line, 0), count);
+        }
+        for (line, true_count, false_count, condition) in
+            [(2, 0, 8, "magic == 0xCAFE"), (9, 0, 8, "len > 0"), (11, 4, 4, "done")]
+        {
+            data.branches.insert(
+                location(line, 7),
+                BranchInfo {
+                    true_count,
+                    false_count,
+                    end_line: line,
+                    end_column: 20,
+                    condition: Some(condition.to_string()),
+                },
+            );
+        }
+        data.functions.insert(
+            "parse".to_string(),
+            FunctionInfo {
+                name: "parse".to_string(),
+                file: PathBuf::from("/src/parse.c"),
+                start_line: 1,
+                end_line: 12,
+                called: 8,
+            },
+        );
+
+        let report = FrontierReport::new(&data);
+        assert_eq!(report.branches.len(), 2);
+        assert_eq!(report.branches[0].location.line, 2);
+        assert_eq!(report.branches[0].blocked_lines, vec![3, 4, 6]);
+        assert_eq!(report.branches[0].untaken, BranchArm::True);
+        // Line 14 is outside the function
+        assert_eq!(report.branches[1].blocked_lines, vec![10]);
+
+        let text = report.to_string();
+        assert!(text.contains(
+            "      3 lines  /src/parse.c:2:7  true arm never taken for `magic == 0xCAFE` (false taken 8 times, in parse)"
+        ));
+    }
+
+    #[test]
+    fn test_blocked_lines_bounded_by_regions() {
+        //  1 int f(int x) {
+        //  2   if (x > 0) {     false arm never taken, the body falls through
+        //  3     x++;
+        //  4   }
+        //  5   if (x == 42) {   true arm never taken
+        //  6     x = 0;
+        //  7     x--;
+        //  8   }
+        //  9   return x;
+        // 10   x = 1;
+        // 11 }
+        let mut data = CoverageData::default();
+        for (line, count) in [(1, 8), (2, 8), (3, 8), (4, 8), (5, 8), (6, 0), (7, 0), (8, 0), (9, 8), (10, 0)] {
+            data.lines.insert(location(line, 0), count);
+        }
+        for (line, column, end_column, true_count, false_count) in [(2, 7, 12, 8, 0), (5, 7, 14, 0, 8)] {
+            data.branches.insert(
+                location(line, column),
+                BranchInfo {
+                    true_count,
+                    false_count,
+                    end_line: line,
+                    end_column,
+                    condition: None,
+                },
+            );
+        }
+        for (start, end_line, end_column, count) in
+            [((1, 14), 11, 2, 8), ((2, 14), 4, 4, 8), ((4, 4), 11, 2, 8), ((5, 16), 8, 4, 0), ((10, 3), 10, 9, 0)]
+        {
+            data.regions.push(RegionInfo {
+                start: location(start.0, start.1),
+                end_line,
+                end_column,
+                count,
+            });
+        }
+
+        let report = FrontierReport::new(&data);
+        assert_eq!(report.branches[0].location.line, 5);
+        assert_eq!(report.branches[0].blocked_lines, vec![6, 7, 8]);
+        // Not the dead code behind the `if (x == 42)` or the `return`
+        assert_eq!(report.branches[1].location.line, 2);
+        assert!(report.branches[1].blocked_lines.is_empty());
+    }
+}
@@ -0,0 +1,301 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::coverage::{percentage, BranchArm, BranchEdge, CoverageData, FileSummary, FunctionInfo};
+use super::coverage_report::escape_markup;
+use super::coverage_visualization::coverage_chart_svg;
+
+/// Stylesheet inlined into every page so the report works straight from disk
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; }
+th, td { padding: 2px 10px; text-align: left; }
+th { background: #eee; }
+.summary td.pct { text-align: right; }
+.high { background: #c8f0c8; }
+.medium { background: #fff0b0; }
+.low { background: #f8c8c8; }
+.source { font-family: monospace; white-space: pre; }
+.source td { padding: 0 8px; }
+.source td.line, .source td.count { text-align: right; color: #666; }
+.source tr.hit td.code { background: #dff5df; }
+.source tr.miss td.code { background: #fadada; }
+.arm { display: inline-block; margin-right: 4px; padding: 0 3px; border-radius: 3px; }
+.arm.hit { background: #9ddc9d; }
+.arm.miss { background: #f09a9a; }
+";
+
+/// Write a self-contained HTML coverage report into `output_dir`: an `index.html` with
+/// per-file totals and the coverage-over-time chart, and one annotated source page per
+/// file. Returns the path of the index page.
+pub fn generate_html_report(
+    coverage_data: &CoverageData,
+    coverage_history: &[f64],
+    output_dir: &Path,
+) -> io::Result<PathBuf> {
+    fs::create_dir_all(output_dir)?;
+
+    let summaries = coverage_data.file_summaries();
+    let edges = coverage_data.branch_edges();
+    let show_mcdc = summaries.values().any(|s| s.total_conditions > 0);
+
+    let mut pages = Vec::new();
+    for (index, (file, summary)) in summaries.iter().enumerate() {
+        let page = page_name(index, file);
+        let html = file_page(coverage_data, &edges, file, summary);
+        fs::write(output_dir.join(&page), html)?;
+        pages.push((file, summary, page));
+    }
+
+    let mut html = page_header("Coverage Report");
+    html.push_str("<h1>Coverage Report</h1>\n");
+
+    if !coverage_history.is_empty() {
+        let svg = coverage_chart_svg(coverage_history)
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        html.push_str("<div class=\"chart\">\n");
+        html.push_str(&svg);
+        html.push_str("\n</div>\n");
+    }
+
+    html.push_str("<table class=\"summary\">\n<tr><th>File</th><th>Lines</th><th>Branches</th><th>Functions</th>");
+    if show_mcdc {
+        html.push_str("<th>MC/DC</th>");
+    }
+    html.push_str("</tr>\n");
+
+    let mut total = FileSummary::default();
+    for (file, summary, page) in &pages {
+        let _ = write!(
+            html,
+            "<tr><td><a href=\"{}\">{}</a></td>",
+            escape_markup(page),
+            escape_markup(&file.display().to_string())
+        );
+        html.push_str(&summary_cells(summary, show_mcdc));
+        html.push_str("</tr>\n");
+        total.add(summary);
+    }
+    html.push_str("<tr><th>Total</th>");
+    html.push_str(&summary_cells(&total, show_mcdc));
+    html.push_str("</tr>\n</table>\n");
+    html.push_str("</body>\n</html>\n");
+
+    let index = output_dir.join("index.html");
+    fs::write(&index, html)?;
+    Ok(index)
+}
+
+/// Annotated source listing for one file
+fn file_page(
+    coverage_data: &CoverageData,
+    edges: &[BranchEdge],
+    file: &Path,
+    summary: &FileSummary,
+) -> String {
+    let title = file.display().to_string();
+    let mut html = page_header(&title);
+    let _ = writeln!(html, "<p><a href=\"index.html\">&larr; Index</a></p>\n<h1>{}</h1>", escape_markup(&title));
+
+    html.push_str("<table class=\"summary\">\n<tr><th>Lines</th><th>Branches</th><th>Functions</th>");
+    let show_mcdc = summary.total_conditions > 0;
+    if show_mcdc {
+        html.push_str("<th>MC/DC</th>");
+    }
+    html.push_str("</tr>\n<tr>");
+    html.push_str(&summary_cells(summary, show_mcdc));
+    html.push_str("</tr>\n</table>\n");
+
+    let mut functions: Vec<&FunctionInfo> = coverage_data
+        .functions
+        .values()
+        .filter(|function| function.file == file)
+        .collect();
+    functions.sort_by_key(|function| (function.start_line, &function.name));
+    if !functions.is_empty() {
+        html.push_str("<h2>Functions</h2>\n<table>\n<tr><th>Function</th><th>Lines</th><th>Calls</th></tr>\n");
+        for function in functions {
+            let _ = writeln!(
+                html,
+                "<tr class=\"{}\"><td><a href=\"#L{}\">{}</a></td><td>{}-{}</td><td>{}</td></tr>",
+                if function.called > 0 { "high" } else { "low" },
+                function.start_line,
+                escape_markup(&function.name),
+                function.start_line,
+                function.end_line,
+                function.called
+            );
+        }
+        html.push_str("</table>\n");
+    }
+
+    let line_counts: HashMap<u32, u64> = coverage_data
+        .lines
+        .iter()
+        .filter(|(location, _)| location.file == file)
+        .map(|(location, count)| (location.line, *count))
+        .collect();
+    let mut line_edges: BTreeMap<u32, Vec<&BranchEdge>> = BTreeMap::new();
+    for edge in edges.iter().filter(|edge| edge.location.file == file) {
+        line_edges.entry(edge.location.line).or_default().push(edge);
+    }
+
+    html.push_str("<h2>Source</h2>\n");
+    match fs::read_to_string(file) {
+        Ok(source) => {
+            html.push_str("<table class=\"source\">\n");
+            for (index, code) in source.lines().enumerate() {
+                let line = index as u32 + 1;
+                let (class, count) = match line_counts.get(&line) {
+                    Some(0) => ("miss", "0".to_string()),
+                    Some(count) => ("hit", count.to_string()),
+                    None => ("", String::new()),
+                };
+                let _ = writeln!(
+                    html,
+                    "<tr id=\"L{line}\" class=\"{class}\"><td class=\"line\">{line}</td><td class=\"count\">{count}</td><td class=\"branches\">{}</td><td class=\"code\">{}</td></tr>",
+                    branch_arms(line_edges.get(&line).map_or(&[][..], Vec::as_slice)),
+                    escape_markup(code)
+                );
+            }
+            html.push_str("</table>\n");
+        }
+        Err(e) => {
+            let _ = writeln!(html, "<p>Source not available: {}</p>", escape_markup(&e.to_string()));
+        }
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// One marker per branch arm, green if it was taken and red if not
+fn branch_arms(edges: &[&BranchEdge]) -> String {
+    let mut html = String::new();
+    for edge in edges {
+        let label = match edge.arm {
+            BranchArm::True => "T",
+            BranchArm::False => "F",
+        };
+        let taken = if edge.reached {
+            edge.count.to_string()
+        } else {
+            "-".to_string()
+        };
+        let _ = write!(
+            html,
+            "<span class=\"arm {}\" title=\"condition at column {}: {} arm taken {} times\">{} {}</span>",
+            if edge.count > 0 { "hit" } else { "miss" },
+            edge.location.column,
+            if edge.arm == BranchArm::True { "true" } else { "false" },
+            edge.count,
+            label,
+            taken
+        );
+    }
+    html
+}
+
+fn summary_cells(summary: &FileSummary, show_mcdc: bool) -> String {
+    let mut cells = String::new();
+    cells.push_str(&pct_cell(summary.covered_lines, summary.total_lines));
+    cells.push_str(&pct_cell(summary.covered_branches, summary.total_branches));
+    cells.push_str(&pct_cell(summary.covered_functions, summary.total_functions));
+    if show_mcdc {
+        cells.push_str(&pct_cell(summary.covered_conditions, summary.total_conditions));
+    }
+    cells
+}
+
+/// Percentage cell shaded by how well covered it is, `-` if there is nothing to cover
+fn pct_cell(covered: usize, total: usize) -> String {
+    if total == 0 {
+        return "<td class=\"pct\">-</td>".to_string();
+    }
+    let pct = percentage(covered, total);
+    let class = if pct >= 80.0 {
+        "high"
+    } else if pct >= 50.0 {
+        "medium"
+    } else {
+        "low"
+    };
+    format!("<td class=\"pct {}\">{:.1}% ({}/{})</td>", class, pct, covered, total)
+}
+
+pub(crate) fn page_header(title: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n",
+        escape_markup(title),
+        STYLE
+    )
+}
+
+/// File name for a source file's page, unique through the index prefix
+fn page_name(index: usize, file: &Path) -> String {
+    let name: String = file
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+        .collect();
+    format!("{:03}_{}.html", index, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BranchInfo, Location};
+
+    #[test]
+    fn test_html_report() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let source = dir.path().join("a.c");
+        fs::write(&source, "int f(int x) {\n  if (x < 3)\n    return 1;\n  return 0;\n}\n")?;
+
+        let mut data = CoverageData::default();
+        for (line, count) in [(1, 2), (2, 2), (3, 0), (4, 2)] {
+            data.lines.insert(
+                Location {
+                    file: source.clone(),
+                    line,
+                    column: 0,
+                },
+                count,
+            );
+        }
+        data.branches.insert(
+            Location {
+                file: source.clone(),
+                line: 2,
+                column: 7,
+            },
+            BranchInfo {
+                true_count: 0,
+                false_count: 2,
+                end_line: 2,
+                end_column: 12,
+                condition: None,
+            },
+        );
+
+        let report_dir = dir.path().join("html");
+        let index = generate_html_report(&data, &[25.0, 75.0], &report_dir)?;
+        let index_html = fs::read_to_string(&index)?;
+        assert!(index_html.contains("<svg"));
+        assert!(index_html.contains("href=\"000_a.c.html\""));
+        assert!(index_html.contains("75.0% (3/4)"));
+        assert!(index_html.contains("50.0% (1/2)"));
+
+        let page = fs::read_to_string(report_dir.join("000_a.c.html"))?;
+        assert!(page.contains("<tr id=\"L3\" class=\"miss\">"));
+        assert!(page.contains("if (x &lt; 3)"));
+        assert!(page.contains("<span class=\"arm miss\""));
+        assert!(page.contains(">F 2</span>"));
+        Ok(())
+    }
+}
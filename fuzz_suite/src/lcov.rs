@@ -0,0 +1,253 @@
+//! Reader for LCOV tracefiles, e.g. from `lcov --capture`, `llvm-cov export -format=lcov`
+//! or our own `generate_lcov`.
+//!
+//! LCOV has no columns, so branch conditions are keyed by their order on the line:
+//! `BRDA:<line>,<block>,<branch>,<taken>` records with branch numbers `2n` and `2n + 1`
+//! become the true and false arms of one condition. JSON data keeps real columns, so
+//! set operations and diffs match conditions by their order on the line instead.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+
+use super::coverage::{BranchInfo, CoverageData, FunctionInfo, Location, McdcInfo};
+
+/// Build coverage data from the text of an LCOV tracefile
+pub fn parse_lcov(text: &str) -> io::Result<CoverageData> {
+    let mut data = CoverageData::default();
+    let mut file: Option<PathBuf> = None;
+    // Function start and end lines of the current file, by name
+    let mut function_lines: HashMap<String, (u32, u32)> = HashMap::new();
+    // Column assigned to each (line, block, condition) of the current file
+    let mut conditions: HashMap<(u32, String, u64), u32> = HashMap::new();
+    let mut conditions_per_line: HashMap<u32, u32> = HashMap::new();
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid LCOV record on line {}: {:?}", number + 1, line),
+            )
+        };
+
+        if line == "end_of_record" {
+            file = None;
+            function_lines.clear();
+            conditions.clear();
+            conditions_per_line.clear();
+            continue;
+        }
+        let (tag, value) = line.split_once(':').unwrap_or((line, ""));
+        if tag == "SF" {
+            file = Some(PathBuf::from(value));
+            continue;
+        }
+        // Test names, summaries and anything newer versions add carry nothing we need
+        let Some(current) = &file else {
+            continue;
+        };
+        let fields: Vec<&str> = value.split(',').collect();
+
+        match tag {
+            "DA" => {
+                let (Some(line), Some(count)) = (field(&fields, 0), field(&fields, 1)) else {
+                    return Err(invalid());
+                };
+                let location = Location {
+                    file: current.clone(),
+                    line: line as u32,
+                    column: 0,
+                };
+                *data.lines.entry(location).or_insert(0) += count;
+            }
+            "FN" => {
+                // Either `FN:<start>,<name>` or, since lcov 2.0, `FN:<start>,<end>,<name>`
+                let Some(start) = field(&fields, 0) else {
+                    return Err(invalid());
+                };
+                let (end, name) = match field(&fields, 1) {
+                    Some(end) if fields.len() > 2 => (end, fields[2..].join(",")),
+                    _ => (start, fields[1..].join(",")),
+                };
+                function_lines.insert(name, (start as u32, end as u32));
+            }
+            "FNDA" => {
+                let Some(count) = field(&fields, 0) else {
+                    return Err(invalid());
+                };
+                let name = fields[1..].join(",");
+                let (start_line, end_line) = function_lines.get(&name).copied().unwrap_or((0, 0));
+                data.functions
+                    .entry(name.clone())
+                    .or_insert_with(|| FunctionInfo {
+                        name,
+                        file: current.clone(),
+                        start_line,
+                        end_line,
+                        called: 0,
+                    })
+                    .called += count;
+            }
+            "BRDA" => {
+                let (Some(line), Some(block), Some(branch)) =
+                    (field(&fields, 0), fields.get(1), field(&fields, 2))
+                else {
+                    return Err(invalid());
+                };
+                // `-` means the condition was never evaluated
+                let taken = match fields.get(3) {
+                    Some(&"-") => 0,
+                    Some(taken) => taken.parse().map_err(|_| invalid())?,
+                    None => return Err(invalid()),
+                };
+                let line = line as u32;
+                let column = *conditions
+                    .entry((line, block.to_string(), branch / 2))
+                    .or_insert_with(|| {
+                        let next = conditions_per_line.entry(line).or_insert(0);
+                        *next += 1;
+                        *next - 1
+                    });
+                let info = data.branches
+                    .entry(Location {
+                        file: current.clone(),
+                        line,
+                        column,
+                    })
+                    .or_insert_with(|| BranchInfo {
+                        true_count: 0,
+                        false_count: 0,
+                        end_line: line,
+                        end_column: column,
+                        condition: None,
+                    });
+                if branch % 2 == 0 {
+                    info.true_count += taken;
+                } else {
+                    info.false_count += taken;
+                }
+            }
+            "MCDC" => {
+                // `MCDC:<line>,<group size>,<sense>,<taken>,<index>,<expression>`
+                let (Some(line), Some(group_size), Some(taken), Some(index)) = (
+                    field(&fields, 0),
+                    field(&fields, 1),
+                    field(&fields, 3),
+                    field(&fields, 4),
+                ) else {
+                    return Err(invalid());
+                };
+                let decision = data.mcdc
+                    .entry(Location {
+                        file: current.clone(),
+                        line: line as u32,
+                        column: 0,
+                    })
+                    .or_insert_with(|| McdcInfo {
+                        end_line: line as u32,
+                        end_column: 0,
+                        independence_pairs: vec![true; group_size as usize],
+                    });
+                // A condition is covered only if both its true and false senses were
+                if let Some(covered) = decision.independence_pairs.get_mut(index as usize) {
+                    *covered &= taken > 0;
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(data)
+}
+
+fn field(fields: &[&str], idx: usize) -> Option<u64> {
+    fields.get(idx)?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    const TRACEFILE: &str = "\
+TN:unit_tests
+SF:/src/a.c
+FN:1,5,check
+FN:7,main
+FNDA:2,check
+FNDA:1,main
+FNF:2
+FNH:2
+DA:1,2
+DA:2,2
+DA:3,0
+DA:8,1
+LF:4
+LH:3
+BRDA:2,0,0,0
+BRDA:2,0,1,2
+BRDA:2,1,0,-
+BRDA:2,1,1,-
+BRF:4
+BRH:1
+MCDC:2,2,t,1,0,x > 5
+MCDC:2,2,f,1,0,x > 5
+MCDC:2,2,t,1,1,y
+MCDC:2,2,f,0,1,y
+end_of_record
+SF:/src/b.c
+DA:4,7
+end_of_record
+";
+
+    #[test]
+    fn test_parse_lcov() {
+        let data = parse_lcov(TRACEFILE).unwrap();
+        let a = Path::new("/src/a.c");
+
+        assert_eq!(data.line_count(a, 2), Some(2));
+        assert_eq!(data.line_count(a, 3), Some(0));
+        assert_eq!(data.line_count(Path::new("/src/b.c"), 4), Some(7));
+        assert_eq!((data.covered_lines(), data.total_lines()), (4, 5));
+
+        let check = &data.functions["check"];
+        assert_eq!((check.start_line, check.end_line, check.called), (1, 5, 2));
+        assert_eq!(data.functions["main"].start_line, 7);
+
+        let location = |column| Location {
+            file: a.to_path_buf(),
+            line: 2,
+            column,
+        };
+        let first = &data.branches[&location(0)];
+        assert_eq!((first.true_count, first.false_count), (0, 2));
+        assert!(!data.branches[&location(1)].reached());
+        assert_eq!(data.branch_arms(), (4, 1));
+
+        assert_eq!(data.mcdc_conditions(), (2, 1));
+    }
+
+    #[test]
+    fn test_roundtrip_through_generate_lcov() -> io::Result<()> {
+        let data = parse_lcov(TRACEFILE)?;
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("coverage.lcov");
+        crate::generate_lcov(&data, &path)?;
+
+        let reread = parse_lcov(&std::fs::read_to_string(&path)?)?;
+        assert_eq!(reread.lines, data.lines);
+        assert_eq!(reread.branch_arms(), data.branch_arms());
+        assert_eq!(reread.functions["check"].called, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_record() {
+        let err = parse_lcov("SF:a.c\nDA:x,1\n").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("line 2"));
+    }
+}
@@ -1,30 +1,83 @@
 // lib.rs
 use std::path::{Path, PathBuf};
-use std::process::Command;
-use std::collections::{HashMap, HashSet};
-use std::fs::{File, OpenOptions};
+use std::fs::File;
 use std::io::{self, Write};
 use std::time::SystemTime;
 
+mod campaign;
+mod campaign_file;
 mod coverage;
+mod coverage_diff;
 mod coverage_visualization;
 mod coverage_report;
 mod compiler;
+mod control_flow;
+mod error;
+mod file_filter;
+mod frontier;
+mod html_report;
+mod lcov;
+mod llvm_export;
+mod minimize;
+mod parallel;
+mod build_wrapper;
+mod sanitizer;
 mod simple_fuzzer;
+mod stats_log;
+mod status;
+mod target;
+mod toolchain;
+mod triage;
 
-pub use coverage::{Coverage, CoverageData, Location};
-pub use coverage_visualization::plot_coverage;
-pub use coverage_report::{generate_lcov, CoverageReport};
-pub use compiler::{CompilerConfig, Language};
-pub use simple_fuzzer::{RandomFuzzer, Fuzzer};
+pub use campaign::{load_inputs, Campaign, CampaignConfig, FuzzerKind, Progress, StopReason};
+pub use campaign_file::{load_campaign_file, parse_campaign_file, CampaignFile};
+pub use coverage::{
+    BranchArm, BranchEdge, BranchInfo, Coverage, CoverageData, FileSummary, FunctionInfo,
+    Location, McdcInfo, RegionInfo,
+};
+pub use coverage_diff::{generate_html_diff, CoverageDiff, FileDiff};
+pub use file_filter::FileFilter;
+pub use frontier::{FrontierBranch, FrontierReport};
+pub use html_report::generate_html_report;
+pub use lcov::parse_lcov;
+pub use llvm_export::parse_llvm_export;
+pub use minimize::{minimize_corpus, reduce_bytes, reduce_input};
+pub use parallel::{ParallelCampaign, ParallelSummary};
+pub use coverage_visualization::{
+    coverage_chart_svg, plot_coverage, plot_cumulative_coverage, plot_series, plot_stats,
+    CoveragePoint, CoverageSeries, MarkerKind, PlotMarker, PlotOptions, XAxis,
+};
+pub use coverage_report::{
+    format_source_with_coverage, generate_cobertura, generate_lcov, generate_sonarqube,
+    CoverageReport,
+};
+pub use compiler::{detect_language, CompilerConfig, Language, SanitizerBuild};
+pub use control_flow::{render_dot_svg, BasicBlock, ControlFlowGraph, FunctionCfg};
+pub use error::{parse_diagnostics, CompileError, Diagnostic, Severity};
+pub use build_wrapper::{write_compiler_wrappers, WrappedBuild};
+pub use sanitizer::{sanitizer_env, sanitizer_flags, validate_sanitizers, Sanitizer};
+pub use target::{BuildOutcome, BuildTarget, TranslationUnit};
+pub use stats_log::{read_stats, StatsLog, StatsRecord, STATS_FILE};
+pub use status::{render_line, render_screen, CampaignStats, StatusScreen};
+pub use toolchain::Toolchain;
+pub use triage::{crash_signature, group_crashes, CrashGroup};
+pub use simple_fuzzer::{
+    BinaryProgramRunner, Execution, Fuzzer, InputMode, MutationFuzzer, Mutator, Outcome,
+    PrintRunner, ProgramRunner, RandomFuzzer, Runner,
+};
 
 
 #[derive(Debug, Clone)]
 pub struct CoverageConfig {
     pub output_dir: PathBuf,
     pub source_dir: PathBuf,
+    /// Instrumented binary the profile data belongs to
+    pub binary: Option<PathBuf>,
     pub profile_data: PathBuf,
     pub compiler: CompilerConfig,
+    pub toolchain: Toolchain,
+    /// Which files count towards coverage totals
+    pub file_filter: FileFilter,
 }
 
 impl Default for CoverageConfig {
@@ -32,14 +85,18 @@ impl Default for CoverageConfig {
         CoverageConfig {
             output_dir: PathBuf::from("coverage"),
             source_dir: PathBuf::from("src"),
+            binary: None,
             profile_data: PathBuf::from("coverage.profdata"),
             compiler: CompilerConfig::default(),
+            toolchain: Toolchain::default(),
+            file_filter: FileFilter::default(),
         }
     }
 }
 
 /// Initialize coverage tracking for C/C++ programs
 pub fn init_coverage(config: &CoverageConfig) -> io::Result<()> {
+    config.file_filter.validate()?;
     std::fs::create_dir_all(&config.output_dir)?;
     std::env::set_var("LLVM_PROFILE_FILE", config.profile_data.to_str().unwrap());
     Ok(())
@@ -50,10 +107,20 @@ pub fn compile_with_coverage(
     source_file: &Path,
     output_file: &Path,
     config: &CoverageConfig,
-) -> io::Result<()> {
+) -> Result<(), CompileError> {
     config.compiler.compile_with_coverage(source_file, output_file)
 }
 
+/// Compile and link a multi-file target with coverage instrumentation
+pub fn compile_target_with_coverage(
+    target: &BuildTarget,
+    build_dir: &Path,
+    output_file: &Path,
+    config: &CoverageConfig,
+) -> Result<BuildOutcome, CompileError> {
+    target.build(&config.compiler, build_dir, output_file)
+}
+
 /// Save a crashing input to a file
 pub fn save_crash(
     input: &str,
@@ -0,0 +1,405 @@
+//! Parser for the JSON written by `llvm-cov export -format=text`.
+//!
+//! Segments, branches and function regions are positional arrays in that format:
+//! - segment: `[line, col, count, hasCount, isRegionEntry, isGapRegion]`
+//! - branch:  `[lineStart, colStart, lineEnd, colEnd, trueCount, falseCount, fileId, expandedFileId, kind]`
+//! - region:  `[lineStart, colStart, lineEnd, colEnd, count, fileId, expandedFileId, kind]`
+//! - MC/DC:   `[lineStart, colStart, lineEnd, colEnd, expandedFileId, kind, [conditionCovered...]]`
+
+use std::io;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::coverage::{BranchInfo, CoverageData, FunctionInfo, Location, McdcInfo, RegionInfo};
+
+/// `kind` of a plain code region in function region arrays
+const CODE_REGION: u64 = 0;
+
+#[derive(Debug, Deserialize)]
+struct Export {
+    data: Vec<ExportData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExportData {
+    #[serde(default)]
+    files: Vec<ExportFile>,
+    #[serde(default)]
+    functions: Vec<ExportFunction>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExportFile {
+    filename: PathBuf,
+    #[serde(default)]
+    segments: Vec<Vec<Value>>,
+    #[serde(default)]
+    branches: Vec<Vec<Value>>,
+    /// Only present for binaries built with `-fcoverage-mcdc` (LLVM 18+)
+    #[serde(default)]
+    mcdc_records: Vec<Vec<Value>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExportFunction {
+    name: String,
+    count: u64,
+    #[serde(default)]
+    regions: Vec<Vec<Value>>,
+    #[serde(default)]
+    filenames: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    line: u32,
+    count: u64,
+    has_count: bool,
+    is_region_entry: bool,
+    is_gap: bool,
+}
+
+impl Segment {
+    fn parse(values: &[Value]) -> Option<Self> {
+        Some(Segment {
+            line: int(values, 0)? as u32,
+            count: int(values, 2)?,
+            has_count: flag(values, 3)?,
+            is_region_entry: flag(values, 4)?,
+            // Older LLVM releases don't emit the gap flag
+            is_gap: flag(values, 5).unwrap_or(false),
+        })
+    }
+
+    fn is_start_of_region(&self) -> bool {
+        !self.is_gap && self.has_count && self.is_region_entry
+    }
+}
+
+/// Build coverage data from an `llvm-cov export` JSON document
+pub fn parse_llvm_export(json: &str) -> io::Result<CoverageData> {
+    let export: Export = serde_json::from_str(json)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    let mut data = CoverageData::default();
+
+    for export_data in export.data {
+        for file in export_data.files {
+            parse_file(&file, &mut data);
+            parse_mcdc(&file, &mut data);
+        }
+        for function in export_data.functions {
+            parse_function(&function, &mut data);
+        }
+    }
+    Ok(data)
+}
+
+fn parse_file(file: &ExportFile, data: &mut CoverageData) {
+    let segments: Vec<Segment> = file
+        .segments
+        .iter()
+        .filter_map(|s| Segment::parse(s))
+        .collect();
+
+    for (line, count) in line_counts(&segments) {
+        let location = Location {
+            file: file.filename.clone(),
+            line,
+            column: 0,
+        };
+        *data.lines.entry(location).or_insert(0) += count;
+    }
+
+    for branch in &file.branches {
+        let (Some(line), Some(column), Some(end_line), Some(end_column), Some(true_count), Some(false_count)) = (
+            int(branch, 0),
+            int(branch, 1),
+            int(branch, 2),
+            int(branch, 3),
+            int(branch, 4),
+            int(branch, 5),
+        ) else {
+            continue;
+        };
+        let location = Location {
+            file: file.filename.clone(),
+            line: line as u32,
+            column: column as u32,
+        };
+
+        // The same condition shows up once per macro expansion; add those up
+        let info = data.branches.entry(location).or_insert_with(|| BranchInfo {
+            true_count: 0,
+            false_count: 0,
+            end_line: end_line as u32,
+            end_column: end_column as u32,
+            condition: None,
+        });
+        info.true_count += true_count;
+        info.false_count += false_count;
+    }
+}
+
+fn parse_mcdc(file: &ExportFile, data: &mut CoverageData) {
+    for record in &file.mcdc_records {
+        let (Some(line), Some(column), Some(end_line), Some(end_column)) =
+            (int(record, 0), int(record, 1), int(record, 2), int(record, 3))
+        else {
+            continue;
+        };
+        // The per-condition flags are the trailing array of booleans
+        let Some(independence_pairs) = record.iter().rev().find_map(|value| {
+            value
+                .as_array()?
+                .iter()
+                .map(|c| c.as_bool())
+                .collect::<Option<Vec<bool>>>()
+        }) else {
+            continue;
+        };
+
+        let location = Location {
+            file: file.filename.clone(),
+            line: line as u32,
+            column: column as u32,
+        };
+        let decision = data.mcdc.entry(location).or_insert_with(|| McdcInfo {
+            end_line: end_line as u32,
+            end_column: end_column as u32,
+            independence_pairs: vec![false; independence_pairs.len()],
+        });
+        // Decisions repeated by macro expansion count as covered if any copy was
+        for (merged, covered) in decision.independence_pairs.iter_mut().zip(independence_pairs) {
+            *merged |= covered;
+        }
+    }
+}
+
+fn parse_function(function: &ExportFunction, data: &mut CoverageData) {
+    let Some(file) = function.filenames.first() else {
+        return;
+    };
+
+    let mut start_line = u32::MAX;
+    let mut end_line = 0;
+    for region in &function.regions {
+        let (Some(line), Some(column), Some(region_end_line), Some(end_column), Some(count), Some(file_id), Some(kind)) = (
+            int(region, 0),
+            int(region, 1),
+            int(region, 2),
+            int(region, 3),
+            int(region, 4),
+            int(region, 5),
+            int(region, 7),
+        ) else {
+            continue;
+        };
+        let Some(filename) = function.filenames.get(file_id as usize) else {
+            continue;
+        };
+
+        if file_id == 0 {
+            start_line = start_line.min(line as u32);
+            end_line = end_line.max(region_end_line as u32);
+        }
+        if kind == CODE_REGION {
+            data.regions.push(RegionInfo {
+                start: Location {
+                    file: filename.clone(),
+                    line: line as u32,
+                    column: column as u32,
+                },
+                end_line: region_end_line as u32,
+                end_column: end_column as u32,
+                count,
+            });
+        }
+    }
+
+    if start_line == u32::MAX {
+        return;
+    }
+    data.functions.insert(
+        function.name.clone(),
+        FunctionInfo {
+            name: function.name.clone(),
+            file: file.clone(),
+            start_line,
+            end_line,
+            called: function.count,
+        },
+    );
+}
+
+/// Execution count of every line that has code, following llvm-cov's own rules:
+/// a line is executable if a counted region starts on it or a counted region wraps
+/// onto it from an earlier line, and its count is the largest of those regions.
+fn line_counts(segments: &[Segment]) -> Vec<(u32, u64)> {
+    let (Some(first), Some(last)) = (segments.first(), segments.last()) else {
+        return vec![];
+    };
+
+    let mut counts = Vec::new();
+    let mut wrapped: Option<Segment> = None;
+    let mut idx = 0;
+    for line in first.line..=last.line {
+        let start = idx;
+        while idx < segments.len() && segments[idx].line == line {
+            idx += 1;
+        }
+        let line_segments = &segments[start..idx];
+
+        let region_starts = line_segments.iter().filter(|s| s.is_start_of_region()).count();
+        let starts_skipped = line_segments
+            .first()
+            .is_some_and(|s| !s.has_count && s.is_region_entry);
+        let mapped = (!starts_skipped
+            && (wrapped.is_some_and(|w| w.has_count) || region_starts > 0))
+            || line_segments.iter().any(|s| s.is_region_entry && s.has_count);
+
+        if mapped {
+            let count = line_segments
+                .iter()
+                .filter(|s| s.is_start_of_region())
+                .map(|s| s.count)
+                .fold(wrapped.map_or(0, |w| w.count), u64::max);
+            counts.push((line, count));
+        }
+
+        if let Some(last) = line_segments.last() {
+            wrapped = Some(*last);
+        }
+    }
+    counts
+}
+
+fn int(values: &[Value], idx: usize) -> Option<u64> {
+    let value = values.get(idx)?;
+    value
+        .as_u64()
+        .or_else(|| value.as_i64().map(|v| v.max(0) as u64))
+        .or_else(|| value.as_bool().map(u64::from))
+}
+
+fn flag(values: &[Value], idx: usize) -> Option<bool> {
+    let value = values.get(idx)?;
+    value.as_bool().or_else(|| value.as_u64().map(|v| v != 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Trimmed `llvm-cov export` output for:
+    //  1 int check(int x) {
+    //  2   if (x > 5)
+    //  3     return 1;
+    //  4   return 0;
+    //  5 }
+    //  6
+    //  7 int main() {
+    //  8   return check(1);
+    //  9 }
+    const EXPORT: &str = r#"{
+      "data": [{
+        "files": [{
+          "filename": "/src/test.c",
+          "segments": [
+            [1, 20, 1, true, true, false],
+            [2, 7, 1, true, true, false],
+            [2, 12, 0, true, false, true],
+            [3, 5, 0, true, true, false],
+            [3, 13, 1, true, false, true],
+            [4, 3, 1, true, true, false],
+            [5, 2, 0, false, false, false],
+            [7, 12, 1, true, true, false],
+            [9, 2, 0, false, false, false]
+          ],
+          "branches": [[2, 7, 2, 12, 0, 1, 0, 0, 4]],
+          "summary": {}
+        }],
+        "functions": [
+          {"name": "check", "count": 1, "filenames": ["/src/test.c"],
+           "regions": [[1, 20, 5, 2, 1, 0, 0, 0], [3, 5, 3, 13, 0, 0, 0, 0], [2, 12, 3, 5, 0, 0, 0, 3]],
+           "branches": []},
+          {"name": "main", "count": 1, "filenames": ["/src/test.c"],
+           "regions": [[7, 12, 9, 2, 1, 0, 0, 0]], "branches": []}
+        ],
+        "totals": {}
+      }],
+      "type": "llvm.coverage.json.export",
+      "version": "2.0.1"
+    }"#;
+
+    #[test]
+    fn test_line_counts() {
+        let segments: Vec<Segment> = serde_json::from_str::<Export>(EXPORT).unwrap().data[0].files[0]
+            .segments
+            .iter()
+            .filter_map(|s| Segment::parse(s))
+            .collect();
+
+        assert_eq!(
+            line_counts(&segments),
+            vec![(1, 1), (2, 1), (3, 0), (4, 1), (5, 1), (7, 1), (8, 1), (9, 1)]
+        );
+    }
+
+    #[test]
+    fn test_parse_export() {
+        let data = parse_llvm_export(EXPORT).unwrap();
+
+        let file = PathBuf::from("/src/test.c");
+        assert_eq!(data.line_count(&file, 2), Some(1));
+        assert_eq!(data.line_count(&file, 3), Some(0));
+        assert_eq!(data.line_count(&file, 6), None);
+        assert_eq!((data.covered_lines(), data.total_lines()), (7, 8));
+
+        let branch = &data.branches[&Location {
+            file: PathBuf::from("/src/test.c"),
+            line: 2,
+            column: 7,
+        }];
+        assert_eq!((branch.true_count, branch.false_count), (0, 1));
+        assert_eq!((branch.end_line, branch.end_column), (2, 12));
+
+        let check = &data.functions["check"];
+        assert_eq!((check.start_line, check.end_line, check.called), (1, 5, 1));
+        assert_eq!(check.file, PathBuf::from("/src/test.c"));
+
+        // Gap regions are dropped, code regions kept
+        assert_eq!(data.regions.len(), 3);
+        assert!(data.regions.iter().any(|r| r.start.line == 3 && r.count == 0));
+    }
+
+    #[test]
+    fn test_parse_mcdc_records() {
+        let json = r#"{"data": [{"files": [{
+            "filename": "/src/a.c",
+            "segments": [],
+            "mcdc_records": [
+                [4, 7, 4, 22, 0, 5, [true, false, true]],
+                [9, 7, 9, 15, 0, 5, [false, false]]
+            ]
+        }], "functions": []}]}"#;
+
+        let data = parse_llvm_export(json).unwrap();
+        let decision = &data.mcdc[&Location {
+            file: PathBuf::from("/src/a.c"),
+            line: 4,
+            column: 7,
+        }];
+        assert_eq!(decision.conditions(), 3);
+        assert_eq!(decision.covered_conditions(), 2);
+        assert_eq!(data.mcdc_conditions(), (5, 2));
+    }
+
+    #[test]
+    fn test_invalid_json() {
+        let err = parse_llvm_export("not json").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -1,128 +1,690 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use fuzz_suite::{
-    Coverage, CoverageConfig, CompilerConfig,
-    Language, compile_with_coverage, RandomFuzzer, Fuzzer
+    compile_with_coverage, crash_signature, detect_language, generate_cobertura,
+    generate_html_diff, generate_html_report, generate_lcov, generate_sonarqube, group_crashes,
+    load_campaign_file, load_inputs, minimize_corpus, plot_stats, reduce_bytes, BuildTarget,
+    Campaign, CampaignConfig, CompilerConfig, ControlFlowGraph, CoverageConfig, CoverageData,
+    CoverageDiff, CoverageReport, FrontierReport, FuzzerKind, InputMode, Language,
+    ParallelCampaign, PlotOptions, ProgramRunner, Sanitizer, Toolchain, XAxis,
 };
-use std::path::PathBuf;
-use std::process::Command;
-use std::fs::File;
-use std::io::Write;
-
-
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    println!("C/C++ Program Fuzzer with Coverage Analysis");
-    println!("==========================================");
-
-    // Setup base directories
-    let example_dir = PathBuf::from("examples/cgi_decode");
-    let source_file = example_dir.join("src/cgi_decode.c");
-    
-    // Create necessary directories
-    let bin_dir = example_dir.join("bin");
-    let crashes_dir = example_dir.join("crashes");
-    let coverage_dir = example_dir.join("coverage");
-    let fuzz_dir = example_dir.join("fuzz_inputs");
-
-    for dir in [&bin_dir, &crashes_dir, &coverage_dir, &fuzz_dir] {
-        std::fs::create_dir_all(dir)?;
-    }
-
-    let output_file = bin_dir.join("cgi_decode");
-
-    println!("Setting up fuzzing configuration...");
-    
-    // Configure compiler
-    let compiler_config = CompilerConfig::new(Language::C)
-        .with_flags(vec![
-            "-DTESTING".to_string(),
-            "-g".to_string(),
-            "-fsanitize=address".to_string(),
-        ]);
-
-    // Configure coverage
-    let coverage_config = CoverageConfig {
-        output_dir: coverage_dir.clone(),
-        source_dir: source_file.parent().unwrap().to_path_buf(),
-        profile_data: coverage_dir.join("cgi_decode.profdata"),
-        compiler: compiler_config,
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Coverage-guided fuzzing for C/C++ programs
+#[derive(Parser)]
+#[command(name = "fuzz_suite", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Compile a target with coverage instrumentation
+    Build(BuildArgs),
+    /// Fuzz a target, keeping inputs that find new coverage
+    Fuzz(FuzzArgs),
+    /// Measure the coverage of a corpus
+    Cov(CovArgs),
+    /// Write a report from a coverage file or plot a campaign's stats
+    Report(ReportArgs),
+    /// Shrink a crashing input while it still crashes the same way
+    Reduce(ReduceArgs),
+    /// Group crashing inputs by their crash signature
+    Triage(TriageArgs),
+    /// Keep the smallest subset of a corpus with the same coverage
+    Cmin(CminArgs),
+    /// Run inputs through a target and print the outcome of each
+    Replay(ReplayArgs),
+}
+
+/// How to compile a target from source
+#[derive(Args)]
+struct CompileOptions {
+    /// Extra compiler flag, e.g. --flag=-DTESTING (repeatable)
+    #[arg(long = "flag", allow_hyphen_values = true)]
+    flags: Vec<String>,
+    /// Instrument for MC/DC coverage
+    #[arg(long)]
+    mcdc: bool,
+}
+
+/// The binary under test and how inputs reach it
+#[derive(Args)]
+struct TargetArgs {
+    /// Coverage-instrumented binary to run
+    #[arg(short, long)]
+    binary: Option<PathBuf>,
+    /// How inputs reach the target: arg, stdin or file
+    #[arg(long, default_value = "arg")]
+    input_mode: InputMode,
+    /// Per-run timeout in milliseconds
+    #[arg(long, default_value_t = 1000)]
+    timeout_ms: u64,
+    /// Sanitizers the target is built with (comma-separated or repeated)
+    #[arg(long = "sanitizer", value_delimiter = ',')]
+    sanitizers: Vec<Sanitizer>,
+    /// Arguments passed to the target before the input; `@@` is replaced by the input
+    /// file in file mode
+    #[arg(last = true)]
+    args: Vec<String>,
+}
+
+impl TargetArgs {
+    fn binary(&self) -> Result<&Path, Box<dyn Error>> {
+        self.binary.as_deref().ok_or_else(|| "--binary is required".into())
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+
+    fn runner(&self) -> Result<ProgramRunner, Box<dyn Error>> {
+        Ok(ProgramRunner::new(&self.binary()?.to_string_lossy())
+            .with_args(self.args.clone())
+            .with_input_mode(self.input_mode)
+            .with_timeout(self.timeout())
+            .with_sanitizers(&self.sanitizers))
+    }
+
+    /// Campaign settings for running this target with results in `output_dir`
+    fn campaign(
+        &self,
+        output_dir: &Path,
+        source_dir: &Path,
+        toolchain: &Toolchain,
+    ) -> Result<CampaignConfig, Box<dyn Error>> {
+        Ok(CampaignConfig::new(self.binary()?, output_dir)
+            .with_args(self.args.clone())
+            .with_input_mode(self.input_mode)
+            .with_timeout(self.timeout())
+            .with_sanitizers(self.sanitizers.clone())
+            .with_source_dir(source_dir)
+            .with_toolchain(toolchain))
+    }
+}
+
+#[derive(Args)]
+struct BuildArgs {
+    /// Source files, or a directory to collect them from
+    #[arg(required = true)]
+    sources: Vec<PathBuf>,
+    /// Output binary
+    #[arg(short, long)]
+    output: PathBuf,
+    #[command(flatten)]
+    compile: CompileOptions,
+    /// Sanitizers to build in (comma-separated or repeated)
+    #[arg(long = "sanitizer", value_delimiter = ',')]
+    sanitizers: Vec<Sanitizer>,
+}
+
+#[derive(Args)]
+struct FuzzArgs {
+    /// TOML campaign file describing the target and campaign; --seeds, the limits,
+    /// --seed, --workers and --sync-interval override it, and target, build, output and
+    /// generator options can't be combined with it
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "binary", "input_mode", "timeout_ms", "sanitizers", "args", "sources", "flags", "mcdc",
+            "source_dir", "output_dir", "fuzzer", "min_length", "max_length",
+        ]
+    )]
+    config: Option<PathBuf>,
+    #[command(flatten)]
+    target: TargetArgs,
+    /// Build the target from these sources instead of passing --binary (repeatable)
+    #[arg(long = "source", conflicts_with = "binary")]
+    sources: Vec<PathBuf>,
+    #[command(flatten)]
+    compile: CompileOptions,
+    /// Directory whose sources coverage is reported for [default: directory of the
+    /// first --source, else the current directory]
+    #[arg(long)]
+    source_dir: Option<PathBuf>,
+    /// Where the corpus, crashes, hangs, coverage and stats go
+    #[arg(short, long, default_value = "fuzz_out")]
+    output_dir: PathBuf,
+    /// Input generator: random or mutation
+    #[arg(long, default_value = "random")]
+    fuzzer: FuzzerKind,
+    /// File or directory of seed inputs
+    #[arg(long)]
+    seeds: Option<PathBuf>,
+    /// Stop after this many executions
+    #[arg(long)]
+    max_execs: Option<u64>,
+    /// Stop after this many seconds
+    #[arg(long)]
+    max_time: Option<u64>,
+    /// Stop once no input has found new coverage for this many seconds
+    #[arg(long)]
+    max_idle: Option<u64>,
+    /// Seed of the fuzzer's RNG, to replay an earlier campaign [default: random]
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Fuzz with this many workers in parallel, each in its own worker_NN directory
+    #[arg(long)]
+    workers: Option<usize>,
+    /// How often parallel workers import each other's new inputs, in seconds
+    #[arg(long)]
+    sync_interval: Option<u64>,
+    /// Shortest random input
+    #[arg(long, default_value_t = 1)]
+    min_length: usize,
+    /// Longest random input
+    #[arg(long, default_value_t = 100)]
+    max_length: usize,
+}
+
+#[derive(Args)]
+struct CovArgs {
+    /// Input file or corpus directory
+    corpus: PathBuf,
+    /// Directory whose sources coverage is reported for
+    #[arg(long, default_value = ".")]
+    source_dir: PathBuf,
+    /// Where the coverage reports go
+    #[arg(short, long, default_value = "cov_out")]
+    output_dir: PathBuf,
+    #[command(flatten)]
+    target: TargetArgs,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ReportFormat {
+    Text,
+    Html,
+    Lcov,
+    Cobertura,
+    Sonarqube,
+    Frontier,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum PlotAxis {
+    Execs,
+    Time,
+}
+
+#[derive(Args)]
+struct ReportArgs {
+    /// LCOV or llvm-cov JSON coverage file
+    #[arg(required_unless_present = "stats")]
+    coverage: Option<PathBuf>,
+    #[arg(short, long, value_enum, default_value = "text")]
+    format: ReportFormat,
+    /// Output file, or directory for HTML [default: stdout for text and frontier]
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Earlier coverage file to diff against; text prints the diff, html writes it
+    #[arg(long)]
+    diff: Option<PathBuf>,
+    /// Plot a campaign's fuzzer_stats file to --output instead
+    #[arg(long, conflicts_with = "coverage")]
+    stats: Option<PathBuf>,
+    /// X axis of the stats plot
+    #[arg(long, value_enum, default_value = "execs")]
+    x_axis: PlotAxis,
+    /// Logarithmic x axis for the stats plot
+    #[arg(long)]
+    log_scale: bool,
+    /// Also write the control-flow graphs of this source file as DOT, colored by coverage
+    #[arg(long)]
+    cfg: Option<PathBuf>,
+    /// Also write the call graph of --cfg's source as DOT
+    #[arg(long, requires = "cfg")]
+    call_graph: bool,
+    #[command(flatten)]
+    compile: CompileOptions,
+}
+
+#[derive(Args)]
+struct ReduceArgs {
+    /// Crashing input
+    input: PathBuf,
+    /// Where to write the reduced input [default: <input>.min]
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    #[command(flatten)]
+    target: TargetArgs,
+}
+
+#[derive(Args)]
+struct TriageArgs {
+    /// Crash file or directory of crashes
+    crashes: PathBuf,
+    #[command(flatten)]
+    target: TargetArgs,
+}
+
+#[derive(Args)]
+struct CminArgs {
+    /// Corpus directory
+    corpus: PathBuf,
+    /// Directory to copy the kept inputs to
+    #[arg(short, long)]
+    output: PathBuf,
+    /// Directory whose sources coverage is measured for
+    #[arg(long, default_value = ".")]
+    source_dir: PathBuf,
+    #[command(flatten)]
+    target: TargetArgs,
+}
+
+#[derive(Args)]
+struct ReplayArgs {
+    /// Input file or directory of inputs
+    inputs: PathBuf,
+    #[command(flatten)]
+    target: TargetArgs,
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Build(args) => build(args),
+        Command::Fuzz(args) => fuzz(args),
+        Command::Cov(args) => cov(args),
+        Command::Report(args) => report(args),
+        Command::Reduce(args) => reduce(args),
+        Command::Triage(args) => triage(args),
+        Command::Cmin(args) => cmin(args),
+        Command::Replay(args) => replay(args),
     };
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
 
-    // Set LLVM_PROFILE_FILE to store raw profile data in our coverage directory
-    std::env::set_var(
-        "LLVM_PROFILE_FILE",
-        coverage_dir.join("default.profraw").to_str().unwrap()
-    );
+fn toolchain() -> Toolchain {
+    Toolchain::discover().unwrap_or_else(|e| {
+        eprintln!("Toolchain discovery failed ({}), using tools on PATH", e);
+        Toolchain::default()
+    })
+}
 
-    println!("Compiling program with coverage instrumentation...");
-    compile_with_coverage(&source_file, &output_file, &coverage_config)?;
+fn compiler_config(
+    language: Language,
+    toolchain: &Toolchain,
+    compile: &CompileOptions,
+    sanitizers: &[Sanitizer],
+) -> CompilerConfig {
+    let config = CompilerConfig::new(language)
+        .with_toolchain(toolchain)
+        .with_flags(compile.flags.clone())
+        .with_sanitizers(sanitizers.to_vec());
+    if compile.mcdc {
+        config.with_mcdc()
+    } else {
+        config
+    }
+}
 
-    // Initialize fuzzer and coverage tracking
-    let fuzzer = RandomFuzzer::new(1,10,32,95); // sample strings from printable ASCII range
-    let mut coverage = Coverage::new(coverage_config);
-    let mut cumulative_coverage = Vec::new();
-    let mut crashes = Vec::new();
+/// Compile one source file directly, or several (or a directory) as a multi-file target
+/// with objects cached next to the output. `compiler` gets the language of the sources.
+fn build_binary<F>(sources: &[PathBuf], output: &Path, compiler: F) -> Result<(), Box<dyn Error>>
+where
+    F: FnOnce(Language) -> CompilerConfig,
+{
+    let target = match sources {
+        [dir] if dir.is_dir() => BuildTarget::from_dir(dir)?,
+        _ => BuildTarget::new().with_sources(sources.to_vec()),
+    };
+    let language = target
+        .units
+        .iter()
+        .filter_map(|unit| detect_language(&unit.source))
+        .max_by_key(|language| *language == Language::Cpp)
+        .unwrap_or(Language::C);
+    let config = CoverageConfig {
+        compiler: compiler(language),
+        ..CoverageConfig::default()
+    };
+
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    match sources {
+        [source] if source.is_file() => compile_with_coverage(source, output, &config)?,
+        _ => {
+            let build_dir = output.with_extension("build");
+            let outcome = target.build(&config.compiler, &build_dir, output)?;
+            println!(
+                "Compiled {} units, {} up to date",
+                outcome.compiled.len(),
+                outcome.up_to_date.len()
+            );
+        }
+    }
+    println!("Built {}", output.display());
+    Ok(())
+}
+
+fn build(args: BuildArgs) -> Result<(), Box<dyn Error>> {
+    build_binary(&args.sources, &args.output, |language| {
+        compiler_config(language, &toolchain(), &args.compile, &args.sanitizers)
+    })
+}
+
+fn fuzz(mut args: FuzzArgs) -> Result<(), Box<dyn Error>> {
+    let (mut config, workers) = match &args.config {
+        Some(path) => campaign_from_file(path)?,
+        None => (campaign_from_args(&mut args)?, 1),
+    };
+    let workers = args.workers.unwrap_or(workers);
+    // Limits and seeds given on the command line win over the campaign file
+    if let Some(seeds) = &args.seeds {
+        config = config.with_seed_corpus(seeds);
+    }
+    if let Some(max_execs) = args.max_execs {
+        config = config.with_max_executions(max_execs);
+    }
+    if let Some(max_time) = args.max_time {
+        config = config.with_max_time(Duration::from_secs(max_time));
+    }
+    if let Some(max_idle) = args.max_idle {
+        config = config.with_max_idle(Duration::from_secs(max_idle));
+    }
+    if let Some(seed) = args.seed {
+        config = config.with_seed(seed);
+    }
+    if let Some(sync_interval) = args.sync_interval {
+        config.sync_interval = Duration::from_secs(sync_interval);
+    }
+    if workers > 1 {
+        return fuzz_parallel(config, workers);
+    }
+
+    let mut campaign = Campaign::new(config)?;
+    println!("Random seed: {}", campaign.seed());
+    stop_on_ctrl_c(campaign.stop_flag())?;
+    let reason = campaign.run()?;
 
-    println!("\nStarting fuzzing run with 100 inputs...");
-    println!("----------------------------------------");
+    let config = campaign.config();
+    println!("\nStopped: {}", reason);
+    println!("Random seed: {} (rerun with --seed)", campaign.seed());
+    println!("Corpus: {}", config.corpus_dir().display());
+    println!("Crashes: {}", config.crashes_dir().display());
+    println!("Hangs: {}", config.hangs_dir().display());
+    println!("Coverage reports: {}", config.coverage_dir().display());
+    Ok(())
+}
+
+fn fuzz_parallel(config: CampaignConfig, workers: usize) -> Result<(), Box<dyn Error>> {
+    let parallel = ParallelCampaign::new(config.clone(), workers);
+    println!("Random seed: {}", parallel.seed());
+    stop_on_ctrl_c(parallel.stop_flag())?;
+    let summary = parallel.run()?;
+
+    let mut reasons: Vec<String> = summary.stop_reasons.iter().map(|reason| reason.to_string()).collect();
+    reasons.dedup();
+    println!("\nStopped: {}", reasons.join(", "));
+    println!("Random seed: {} (rerun with --seed)", parallel.seed());
+    println!("Workers: {}", config.output_dir.join("worker_*").display());
+    println!("Merged coverage reports: {}", config.output_dir.join("coverage").display());
+    Ok(())
+}
+
+/// The first Ctrl-C lets the campaign write its reports; a second one exits at once
+fn stop_on_ctrl_c(stop: Arc<AtomicBool>) -> Result<(), ctrlc::Error> {
+    ctrlc::set_handler(move || {
+        if stop.swap(true, Ordering::Relaxed) {
+            std::process::exit(130);
+        }
+    })
+}
+
+/// Load a campaign file, building its target first if it lists sources. Returns the
+/// campaign and its number of workers.
+fn campaign_from_file(path: &Path) -> Result<(CampaignConfig, usize), Box<dyn Error>> {
+    let file = load_campaign_file(path)?;
+    if !file.sources.is_empty() {
+        let compiler = file.config.coverage.compiler.clone();
+        build_binary(&file.sources, &file.config.binary, |_| compiler)?;
+    }
+    Ok((file.config, file.workers))
+}
+
+fn campaign_from_args(args: &mut FuzzArgs) -> Result<CampaignConfig, Box<dyn Error>> {
+    let source_dir = match (&args.source_dir, args.sources.first()) {
+        (Some(dir), _) => dir.clone(),
+        (None, Some(source)) if source.is_dir() => source.clone(),
+        (None, Some(source)) => source.parent().map_or(PathBuf::from("."), Path::to_path_buf),
+        (None, None) => PathBuf::from("."),
+    };
+    // The same tools build the target and read its profiles
+    let toolchain = toolchain();
+    if !args.sources.is_empty() {
+        let binary = args.output_dir.join("bin/target");
+        build_binary(&args.sources, &binary, |language| {
+            compiler_config(language, &toolchain, &args.compile, &args.target.sanitizers)
+        })?;
+        args.target.binary = Some(binary);
+    }
+
+    let mut config = args
+        .target
+        .campaign(&args.output_dir, &source_dir, &toolchain)?
+        .with_fuzzer(args.fuzzer);
+    config.min_length = args.min_length;
+    config.max_length = args.max_length;
+    Ok(config)
+}
+
+fn cov(args: CovArgs) -> Result<(), Box<dyn Error>> {
+    let config = args.target.campaign(&args.output_dir, &args.source_dir, &toolchain())?;
+    let mut campaign = Campaign::new(config)?;
+    for (_, input) in load_inputs(&args.corpus)? {
+        campaign.run_one(&input)?;
+    }
+    campaign.finish()?;
+
+    println!("{}", CoverageReport::new(campaign.coverage_data()));
+    println!("Coverage reports: {}", campaign.config().coverage_dir().display());
+    Ok(())
+}
+
+fn report(args: ReportArgs) -> Result<(), Box<dyn Error>> {
+    if let Some(stats) = &args.stats {
+        let output = args.output.clone().unwrap_or_else(|| PathBuf::from("coverage_over_time.svg"));
+        let x_axis = match args.x_axis {
+            PlotAxis::Execs => XAxis::Executions,
+            PlotAxis::Time => XAxis::ElapsedTime,
+        };
+        let options = PlotOptions::default().with_x_axis(x_axis).with_log_scale(args.log_scale);
+        plot_stats(stats, &options, &output)?;
+        println!("Wrote {}", output.display());
+        return Ok(());
+    }
+
+    let coverage = args.coverage.as_deref().ok_or("a coverage file is required")?;
+    let data = CoverageData::load(coverage)?;
+
+    if let Some(before) = &args.diff {
+        let diff = CoverageDiff::new(&CoverageData::load(before)?, &data);
+        match args.format {
+            ReportFormat::Html => {
+                let output = args.output.clone().unwrap_or_else(|| PathBuf::from("coverage_diff.html"));
+                generate_html_diff(&diff, &output)?;
+                println!("Wrote {}", output.display());
+            }
+            _ => write_text(&diff.to_string(), args.output.as_deref())?,
+        }
+    } else {
+        match args.format {
+            ReportFormat::Text => write_text(&CoverageReport::new(&data).to_string(), args.output.as_deref())?,
+            ReportFormat::Frontier => write_text(&FrontierReport::new(&data).to_string(), args.output.as_deref())?,
+            ReportFormat::Html => {
+                let output = args.output.clone().unwrap_or_else(|| PathBuf::from("html"));
+                let index = generate_html_report(&data, &[], &output)?;
+                println!("Wrote {}", index.display());
+            }
+            ReportFormat::Lcov => {
+                let output = args.output.clone().unwrap_or_else(|| PathBuf::from("coverage.lcov"));
+                generate_lcov(&data, &output)?;
+                println!("Wrote {}", output.display());
+            }
+            ReportFormat::Cobertura => {
+                let output = args.output.clone().unwrap_or_else(|| PathBuf::from("cobertura.xml"));
+                generate_cobertura(&data, &output)?;
+                println!("Wrote {}", output.display());
+            }
+            ReportFormat::Sonarqube => {
+                let output = args.output.clone().unwrap_or_else(|| PathBuf::from("sonarqube.xml"));
+                generate_sonarqube(&data, &output)?;
+                println!("Wrote {}", output.display());
+            }
+        }
+    }
+
+    if let Some(source) = &args.cfg {
+        let language = detect_language(source).unwrap_or(Language::C);
+        let compiler = compiler_config(language, &toolchain(), &args.compile, &[]);
+        let graph = ControlFlowGraph::extract(source, &compiler)?;
+        // Written to the current directory as <stem>.cfg.dot and <stem>.calls.dot
+        let stem = source.file_stem().unwrap_or_default().to_string_lossy();
+        let cfg_path = PathBuf::from(format!("{}.cfg.dot", stem));
+        fs::write(&cfg_path, graph.cfg_dot(&data, &fs::canonicalize(source)?))?;
+        println!("Wrote {}", cfg_path.display());
+        if args.call_graph {
+            let call_graph_path = PathBuf::from(format!("{}.calls.dot", stem));
+            fs::write(&call_graph_path, graph.call_graph_dot(&data))?;
+            println!("Wrote {}", call_graph_path.display());
+        }
+    }
+    Ok(())
+}
+
+fn write_text(text: &str, output: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    match output {
+        Some(path) => {
+            fs::write(path, text)?;
+            println!("Wrote {}", path.display());
+        }
+        None => print!("{}", text),
+    }
+    Ok(())
+}
+
+fn reduce(args: ReduceArgs) -> Result<(), Box<dyn Error>> {
+    let runner = args.target.runner()?;
+    let input = fs::read(&args.input)?;
+    let first = runner.execute(&input)?;
+    if !first.crashed() {
+        return Err(format!("{} does not crash the target", args.input.display()).into());
+    }
+    let signature = crash_signature(&String::from_utf8_lossy(&first.stderr));
+    println!("Reducing {} ({} bytes): {}", args.input.display(), input.len(), signature);
+
+    // Only accept candidates that still crash the same way, not any crash
+    let reduced = reduce_bytes(&input, |candidate| {
+        runner.execute(candidate).is_ok_and(|execution| {
+            execution.crashed() && crash_signature(&String::from_utf8_lossy(&execution.stderr)) == signature
+        })
+    });
+
+    let output = args.output.unwrap_or_else(|| {
+        let mut name = args.input.clone().into_os_string();
+        name.push(".min");
+        PathBuf::from(name)
+    });
+    fs::write(&output, &reduced)?;
+    println!("Wrote {} ({} bytes)", output.display(), reduced.len());
+    Ok(())
+}
 
-    // Run 100 fuzz tests
-    for i in 0..100 {
-        if i % 10 == 0 {
-            println!("Processing input {}/100...", i);
+fn triage(args: TriageArgs) -> Result<(), Box<dyn Error>> {
+    let runner = args.target.runner()?;
+    let mut crashes = Vec::new();
+    let mut not_crashing = 0;
+    for (path, input) in load_inputs(&args.crashes)? {
+        // Keep the stderr saved next to crashes by `fuzz` out of the inputs
+        if path.extension().is_some_and(|ext| ext == "stderr") {
+            continue;
         }
+        let execution = runner.execute(&input)?;
+        if execution.crashed() {
+            crashes.push((path, String::from_utf8_lossy(&execution.stderr).into_owned()));
+        } else {
+            not_crashing += 1;
+        }
+    }
 
-        let input = fuzzer.fuzz();
-
-        // Run program with fuzzer input
-        let output = Command::new(&output_file)
-            .arg(&input)
-            .output()?;
-        
-        // Check for crashes
-        if !output.status.success() {
-            // println!("🐛 Found crash with input {}!", i);
-            let crash_file = crashes_dir.join(format!("crash_{}.txt", i));
-            let mut file = File::create(&crash_file)?;
-            file.write_all(input.as_bytes())?;
-            crashes.push(input);
+    let groups = group_crashes(crashes);
+    println!("{} distinct crashes", groups.len());
+    for group in &groups {
+        println!("\n{} ({} inputs)", group.signature, group.inputs.len());
+        for input in &group.inputs {
+            println!("  {}", input.display());
         }
-        
-        // Process coverage
-        coverage.process_coverage()?;
-        cumulative_coverage.push((i + 1, coverage.coverage_percentage()));
-    }
-
-    println!("\nGenerating coverage reports...");
-    println!("-----------------------------");
-
-    // Transform coverage data for plotting
-    let coverage_percentages: Vec<f64> = cumulative_coverage.iter()
-        .map(|(_, coverage)| *coverage)
-        .collect();
-
-    // Generate coverage visualization
-    fuzz_suite::plot_coverage(
-        &coverage_percentages,
-        &coverage_dir.join("coverage_over_time.png"),
-    )?;
-
-    // Generate coverage report
-    let coverage_data = coverage.get_coverage_data();
-    fuzz_suite::generate_lcov(
-        coverage_data,
-        &coverage_dir.join("coverage.lcov"),
-    )?;
-
-    // Print summary
-    println!("\nFuzzing Summary");
-    println!("==============");
-    println!("Final coverage: {:.2}%", coverage.coverage_percentage());
-    println!("Found {} crashes", crashes.len());
-    println!("\nArtifacts written to:");
-    println!("- Coverage plot: {}", coverage_dir.join("coverage_over_time.png").display());
-    println!("- Coverage report: {}", coverage_dir.join("coverage.lcov").display());
-    if !crashes.is_empty() {
-        println!("- Crash files: {}", crashes_dir.display());
     }
+    if not_crashing > 0 {
+        println!("\n{} inputs did not crash", not_crashing);
+    }
+    Ok(())
+}
+
+fn cmin(args: CminArgs) -> Result<(), Box<dyn Error>> {
+    let work_dir = tempfile::tempdir()?;
+    let config = args.target.campaign(work_dir.path(), &args.source_dir, &toolchain())?;
+    let mut campaign = Campaign::new(config)?;
+
+    let (paths, inputs): (Vec<PathBuf>, Vec<Vec<u8>>) = load_inputs(&args.corpus)?.into_iter().unzip();
+    let kept = minimize_corpus(&inputs, |input| campaign.coverage_of(input))?;
+
+    fs::create_dir_all(&args.output)?;
+    for &index in &kept {
+        let name = paths[index].file_name().unwrap_or_default();
+        fs::copy(&paths[index], args.output.join(name))?;
+    }
+    println!("Kept {} of {} inputs in {}", kept.len(), inputs.len(), args.output.display());
+    Ok(())
+}
 
+fn replay(args: ReplayArgs) -> Result<(), Box<dyn Error>> {
+    let runner = args.target.runner()?;
+    for (path, input) in load_inputs(&args.inputs)? {
+        let execution = runner.execute(&input)?;
+        let outcome = if execution.timed_out {
+            "timeout".to_string()
+        } else if execution.crashed() {
+            format!("crash: {}", crash_signature(&String::from_utf8_lossy(&execution.stderr)))
+        } else {
+            format!("exit {}", execution.status.code().unwrap_or(-1))
+        };
+        println!("{}: {} ({:.1?})", path.display(), outcome, execution.duration);
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+        let cli = Cli::try_parse_from(["fuzz_suite", "replay", "crashes", "-b", "bin/target", "--", "-x", "@@"]).unwrap();
+        match cli.command {
+            Command::Replay(args) => {
+                assert_eq!(args.inputs, PathBuf::from("crashes"));
+                assert_eq!(args.target.args, vec!["-x", "@@"]);
+            }
+            _ => panic!("expected replay"),
+        }
+        assert!(Cli::try_parse_from(["fuzz_suite", "fuzz", "--binary", "a", "--source", "a.c"]).is_err());
+        assert!(Cli::try_parse_from(["fuzz_suite", "fuzz", "--config", "c.toml", "--max-execs", "10"]).is_ok());
+        for flags in [&["--fuzzer", "mutation"][..], &["--timeout-ms", "10"], &["--max-length", "5"], &["--", "-x"]] {
+            let args = ["fuzz_suite", "fuzz", "--config", "c.toml"].iter().chain(flags);
+            assert!(Cli::try_parse_from(args).is_err(), "{:?}", flags);
+        }
+    }
+}
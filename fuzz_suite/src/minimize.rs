@@ -0,0 +1,147 @@
+use std::collections::HashSet;
+use std::io;
+
+use super::coverage::{BranchArm, CoverageData, Location};
+
+/// Shrink a failing input with delta debugging (ddmin): keep removing chunks, from
+/// halves down to single characters, as long as `still_fails` holds for what's left
+pub fn reduce_input<F: FnMut(&str) -> bool>(input: &str, mut still_fails: F) -> String {
+    let chars: Vec<char> = input.chars().collect();
+    ddmin(chars, |chars| still_fails(&chars.iter().collect::<String>()))
+        .into_iter()
+        .collect()
+}
+
+/// Like `reduce_input`, for inputs that needn't be UTF-8, removing bytes
+pub fn reduce_bytes<F: FnMut(&[u8]) -> bool>(input: &[u8], still_fails: F) -> Vec<u8> {
+    ddmin(input.to_vec(), still_fails)
+}
+
+fn ddmin<T: Copy, F: FnMut(&[T]) -> bool>(mut items: Vec<T>, mut still_fails: F) -> Vec<T> {
+    let mut granularity = 2;
+
+    while items.len() >= 2 {
+        let chunk = (items.len() / granularity).max(1);
+        let mut reduced = false;
+
+        let mut start = 0;
+        while start < items.len() {
+            let complement: Vec<T> = items[..start]
+                .iter()
+                .chain(&items[(start + chunk).min(items.len())..])
+                .copied()
+                .collect();
+            if still_fails(&complement) {
+                items = complement;
+                granularity = (granularity - 1).max(2);
+                reduced = true;
+                break;
+            }
+            start += chunk;
+        }
+
+        if !reduced {
+            if granularity >= items.len() {
+                break;
+            }
+            granularity = (granularity * 2).min(items.len());
+        }
+    }
+    items
+}
+
+/// Pick a subset of `inputs` with the same combined coverage, preferring inputs that
+/// cover more and, among equals, shorter ones. Returns the indices of the inputs kept,
+/// in their original order.
+pub fn minimize_corpus<T, F>(inputs: &[T], mut coverage_of: F) -> io::Result<Vec<usize>>
+where
+    T: AsRef<[u8]>,
+    F: FnMut(&T) -> io::Result<CoverageData>,
+{
+    let mut covered = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        covered.push(coverage_points(&coverage_of(input)?));
+    }
+
+    let mut order: Vec<usize> = (0..inputs.len()).collect();
+    order.sort_by(|a, b| {
+        covered[*b]
+            .len()
+            .cmp(&covered[*a].len())
+            .then_with(|| inputs[*a].as_ref().len().cmp(&inputs[*b].as_ref().len()))
+    });
+
+    let mut seen: HashSet<(Location, Option<BranchArm>)> = HashSet::new();
+    let mut kept = Vec::new();
+    for index in order {
+        let before = seen.len();
+        seen.extend(covered[index].iter().cloned());
+        if seen.len() > before {
+            kept.push(index);
+        }
+    }
+    kept.sort_unstable();
+    Ok(kept)
+}
+
+/// Executed lines and taken branch arms
+fn coverage_points(coverage_data: &CoverageData) -> HashSet<(Location, Option<BranchArm>)> {
+    let lines = coverage_data
+        .covered_line_locations()
+        .into_iter()
+        .map(|location| (location.clone(), None));
+    let arms = coverage_data
+        .branch_edges()
+        .into_iter()
+        .filter(|edge| edge.count > 0)
+        .map(|edge| (edge.location, Some(edge.arm)));
+    lines.chain(arms).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_reduce_input() {
+        let reduced = reduce_input("abc<script>alert(1)</script>xyz", |input| input.contains("<s"));
+        assert_eq!(reduced, "<s");
+
+        let mut runs = 0;
+        let reduced = reduce_input("xxxxxxxxBxxxxxxxx", |input| {
+            runs += 1;
+            input.contains('B')
+        });
+        assert_eq!(reduced, "B");
+        assert!(runs < 40);
+    }
+
+    #[test]
+    fn test_reduce_bytes() {
+        let reduced = reduce_bytes(&[0xff, b'a', 0x00, 0xfe, b'b'], |input| input.contains(&0x00));
+        assert_eq!(reduced, vec![0x00]);
+    }
+
+    #[test]
+    fn test_minimize_corpus() -> io::Result<()> {
+        // Each character stands for a line the input covers
+        let inputs: Vec<String> = ["ab", "abc", "c", "d", "abcd"].iter().map(|s| s.to_string()).collect();
+        let kept = minimize_corpus(&inputs, |input| {
+            let mut data = CoverageData::default();
+            for (line, c) in "abcd".chars().enumerate() {
+                data.lines.insert(
+                    Location {
+                        file: PathBuf::from("a.c"),
+                        line: line as u32 + 1,
+                        column: 0,
+                    },
+                    u64::from(input.contains(c)),
+                );
+            }
+            Ok(data)
+        })?;
+        assert_eq!(kept, vec![4]);
+        Ok(())
+    }
+}
@@ -0,0 +1,304 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::campaign::{Campaign, CampaignConfig, Progress, StopReason};
+use super::coverage::CoverageData;
+use super::coverage_report::generate_lcov;
+use super::coverage_visualization::{plot_stats, PlotOptions, XAxis};
+use super::html_report::generate_html_report;
+use super::stats_log::StatsLog;
+use super::status::{CampaignStats, StatusScreen};
+
+/// Several campaigns against the same target on their own threads, AFL `-M/-S` style.
+/// Each worker keeps its own corpus, crashes and coverage in `worker_NN/` under the
+/// output directory and periodically imports the inputs the others found. The
+/// supervisor merges their coverage into one map, shows the combined status and writes
+/// the combined reports to `coverage/` and `fuzzer_stats` in the output directory.
+pub struct ParallelCampaign {
+    config: CampaignConfig,
+    workers: usize,
+    seed: u64,
+    stop: Arc<AtomicBool>,
+}
+
+/// Combined result of the workers
+#[derive(Debug, Clone)]
+pub struct ParallelSummary {
+    pub stats: CampaignStats,
+    /// Union of the coverage of every worker
+    pub coverage: CoverageData,
+    /// Why each worker stopped
+    pub stop_reasons: Vec<StopReason>,
+}
+
+impl ParallelCampaign {
+    /// `config` is shared by all workers; its limits apply to each worker separately
+    pub fn new(config: CampaignConfig, workers: usize) -> Self {
+        ParallelCampaign {
+            seed: config.seed.unwrap_or_else(rand::random),
+            config,
+            workers: workers.max(1),
+            stop: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Setting this flag stops every worker after its current run
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.stop)
+    }
+
+    /// Worker N seeds its RNG with this plus N. Which inputs a worker imports depends
+    /// on timing, so only each worker's own inputs replay exactly.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn worker_dir(&self, index: usize) -> PathBuf {
+        self.config.output_dir.join(format!("worker_{:02}", index))
+    }
+
+    /// Configuration of one worker: its own output directory, syncing from the
+    /// corpora of all the others
+    pub fn worker_config(&self, index: usize) -> CampaignConfig {
+        let output_dir = self.worker_dir(index);
+        let coverage_dir = output_dir.join("coverage");
+        let mut config = self.config.clone();
+        config.coverage.profile_data = coverage_dir.join("coverage.profdata");
+        config.coverage.output_dir = coverage_dir;
+        config.output_dir = output_dir;
+        config.seed = Some(self.seed.wrapping_add(index as u64));
+        config.sync_dirs = (0..self.workers)
+            .filter(|&other| other != index)
+            .map(|other| self.worker_dir(other).join("corpus"))
+            .collect();
+        config
+    }
+
+    pub fn run(&self) -> io::Result<ParallelSummary> {
+        let progress: Vec<Arc<Mutex<Progress>>> = (0..self.workers)
+            .map(|_| Arc::new(Mutex::new(Progress::default())))
+            .collect();
+        fs::create_dir_all(&self.config.output_dir)?;
+        let mut status = StatusScreen::new();
+        let mut stats_log = StatsLog::new(&self.config.output_dir);
+        let start = Instant::now();
+        let mut combined = Combined::new(self.workers, self.seed);
+
+        let stop_reasons = thread::scope(|scope| -> io::Result<Vec<StopReason>> {
+            let handles: Vec<_> = progress
+                .iter()
+                .enumerate()
+                .map(|(index, progress)| {
+                    let config = self.worker_config(index);
+                    let progress = Arc::clone(progress);
+                    let stop = Arc::clone(&self.stop);
+                    scope.spawn(move || -> io::Result<StopReason> {
+                        let result = Campaign::new(config).and_then(|campaign| {
+                            campaign
+                                .with_stop_flag(Arc::clone(&stop))
+                                .with_progress(progress)
+                                .run()
+                        });
+                        // One failed worker ends the campaign rather than leaving it
+                        // short-handed
+                        if result.is_err() {
+                            stop.store(true, Ordering::Relaxed);
+                        }
+                        result
+                    })
+                })
+                .collect();
+
+            while !handles.iter().all(|handle| handle.is_finished()) {
+                let stats = combined.update(&progress, start);
+                status.update(&stats)?;
+                stats_log.record(&stats)?;
+                thread::sleep(Duration::from_millis(100));
+            }
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|_| Err(io::Error::other("fuzzing worker panicked")))
+                })
+                .collect()
+        })?;
+
+        let stats = combined.update(&progress, start);
+        let coverage = combined.coverage;
+        status.draw(&stats)?;
+        stats_log.write(&stats)?;
+
+        let coverage_dir = self.config.output_dir.join("coverage");
+        fs::create_dir_all(&coverage_dir)?;
+        generate_lcov(&coverage, &coverage_dir.join("coverage.lcov"))?;
+        generate_html_report(&coverage, &[], &coverage_dir.join("html"))?;
+        let options = PlotOptions::default().with_x_axis(XAxis::ElapsedTime);
+        plot_stats(
+            stats_log.path(),
+            &options,
+            &coverage_dir.join("coverage_over_time.svg"),
+        )
+        .map_err(|e| io::Error::other(e.to_string()))?;
+
+        Ok(ParallelSummary {
+            stats,
+            coverage,
+            stop_reasons,
+        })
+    }
+}
+
+/// The workers' stats and coverage combined
+struct Combined {
+    seed: u64,
+    coverage: CoverageData,
+    /// `Progress::coverage_version` of each worker when `coverage` was merged
+    coverage_versions: Vec<u64>,
+}
+
+impl Combined {
+    fn new(workers: usize, seed: u64) -> Self {
+        Combined {
+            seed,
+            coverage: CoverageData::default(),
+            coverage_versions: vec![0; workers],
+        }
+    }
+
+    /// Sum the workers' stats, counting crashes several workers found once, and
+    /// merge their coverage again if any of them found more
+    fn update(&mut self, progress: &[Arc<Mutex<Progress>>], start: Instant) -> CampaignStats {
+        let mut stats = CampaignStats {
+            start,
+            stage: format!("{} workers", progress.len()),
+            seed: Some(self.seed),
+            ..CampaignStats::default()
+        };
+        let mut crash_signatures = HashSet::new();
+        let mut coverage_changed = false;
+        for (progress, version) in progress.iter().zip(&mut self.coverage_versions) {
+            let progress = progress.lock().unwrap_or_else(|e| e.into_inner());
+            let worker = &progress.stats;
+            stats.executions += worker.executions;
+            stats.corpus_size += worker.corpus_size;
+            stats.hangs += worker.hangs;
+            stats.last_new_path = stats.last_new_path.max(worker.last_new_path);
+            crash_signatures.extend(progress.crash_signatures.iter().cloned());
+            coverage_changed |= progress.coverage_version != *version;
+            *version = progress.coverage_version;
+        }
+        stats.unique_crashes = crash_signatures.len();
+
+        // Counts add up in a union, so merge every worker from scratch
+        if coverage_changed {
+            self.coverage = progress.iter().fold(CoverageData::default(), |coverage, progress| {
+                coverage.union(&progress.lock().unwrap_or_else(|e| e.into_inner()).coverage)
+            });
+        }
+        stats.coverage = self.coverage.summary();
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_worker_config() {
+        let parallel = ParallelCampaign::new(
+            CampaignConfig::new(Path::new("bin/target"), Path::new("out")),
+            3,
+        );
+        let config = parallel.worker_config(1);
+        assert_eq!(config.output_dir, PathBuf::from("out/worker_01"));
+        assert_eq!(config.seed, Some(parallel.seed().wrapping_add(1)));
+        assert_eq!(
+            config.coverage.output_dir,
+            PathBuf::from("out/worker_01/coverage")
+        );
+        assert_eq!(
+            config.sync_dirs,
+            vec![
+                PathBuf::from("out/worker_00/corpus"),
+                PathBuf::from("out/worker_02/corpus")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_combined() {
+        let progress: Vec<Arc<Mutex<Progress>>> = (0..2).map(|_| Arc::new(Mutex::new(Progress::default()))).collect();
+        for (index, signatures) in [["a", "b"], ["b", "c"]].iter().enumerate() {
+            let mut worker = progress[index].lock().unwrap();
+            worker.stats.executions = 10;
+            worker.stats.unique_crashes = 2;
+            worker.crash_signatures = signatures.iter().map(|s| s.to_string()).collect();
+        }
+        // Each worker covers one line, and both cover the region of `main`
+        let line = |line| {
+            let location = |line| crate::coverage::Location {
+                file: PathBuf::from("a.c"),
+                line,
+                column: 0,
+            };
+            let mut coverage = CoverageData::default();
+            coverage.lines.insert(location(line), 1);
+            coverage.regions.push(crate::coverage::RegionInfo {
+                start: location(1),
+                end_line: 5,
+                end_column: 2,
+                count: 1,
+            });
+            coverage
+        };
+        {
+            let mut worker = progress[0].lock().unwrap();
+            worker.coverage = line(1);
+            worker.coverage_version = 1;
+        }
+
+        let mut combined = Combined::new(2, 42);
+        let stats = combined.update(&progress, Instant::now());
+        assert_eq!(stats.executions, 20);
+        assert_eq!(stats.seed, Some(42));
+        // `b` was found by both workers
+        assert_eq!(stats.unique_crashes, 3);
+        assert_eq!(stats.coverage.covered_lines, 1);
+
+        // Coverage is only merged again once a worker reports that it grew
+        progress[1].lock().unwrap().coverage = line(2);
+        assert_eq!(combined.update(&progress, Instant::now()).coverage.covered_lines, 1);
+        progress[1].lock().unwrap().coverage_version = 1;
+        let stats = combined.update(&progress, Instant::now());
+        assert_eq!(stats.coverage.covered_lines, 2);
+        // The region both workers report is counted once
+        assert_eq!((stats.coverage.covered_regions, stats.coverage.total_regions), (1, 1));
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn test_parallel_campaign() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join("out");
+        let config = CampaignConfig::new(Path::new("true"), &output).with_max_executions(20);
+
+        let summary = ParallelCampaign::new(config, 2).run()?;
+        assert_eq!(summary.stats.executions, 40);
+        assert_eq!(summary.stop_reasons, vec![StopReason::MaxExecutions; 2]);
+        assert!(output.join("worker_00/corpus").is_dir());
+        assert!(output.join("worker_01/fuzzer_stats").exists());
+        assert!(output.join("coverage/coverage.lcov").exists());
+        assert!(!fs::read_to_string(output.join(crate::STATS_FILE))?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,215 @@
+use std::str::FromStr;
+
+use super::error::CompileError;
+
+/// Sanitizers that can be compiled into a target alongside coverage instrumentation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Sanitizer {
+    Address,
+    Undefined,
+    Memory,
+    Thread,
+    Leak,
+    Cfi,
+}
+
+/// Pairs of sanitizers whose runtimes cannot be linked into the same binary
+const INCOMPATIBLE: &[(Sanitizer, Sanitizer)] = &[
+    (Sanitizer::Address, Sanitizer::Memory),
+    (Sanitizer::Address, Sanitizer::Thread),
+    (Sanitizer::Memory, Sanitizer::Thread),
+    (Sanitizer::Leak, Sanitizer::Memory),
+    (Sanitizer::Leak, Sanitizer::Thread),
+];
+
+impl Sanitizer {
+    /// Name used in `-fsanitize=`
+    pub fn flag_name(&self) -> &'static str {
+        match self {
+            Sanitizer::Address => "address",
+            Sanitizer::Undefined => "undefined",
+            Sanitizer::Memory => "memory",
+            Sanitizer::Thread => "thread",
+            Sanitizer::Leak => "leak",
+            Sanitizer::Cfi => "cfi",
+        }
+    }
+
+    /// Short name used for binary suffixes
+    pub fn short_name(&self) -> &'static str {
+        match self {
+            Sanitizer::Address => "asan",
+            Sanitizer::Undefined => "ubsan",
+            Sanitizer::Memory => "msan",
+            Sanitizer::Thread => "tsan",
+            Sanitizer::Leak => "lsan",
+            Sanitizer::Cfi => "cfi",
+        }
+    }
+
+    /// Additional flags the sanitizer needs to work (or report usefully)
+    fn required_flags(&self) -> &'static [&'static str] {
+        match self {
+            Sanitizer::Address => &["-fno-omit-frame-pointer"],
+            Sanitizer::Undefined => &["-fno-sanitize-recover=undefined"],
+            Sanitizer::Memory => &["-fsanitize-memory-track-origins", "-fno-omit-frame-pointer"],
+            Sanitizer::Thread => &[],
+            Sanitizer::Leak => &[],
+            // CFI requires LTO and hidden visibility to see the whole class hierarchy
+            Sanitizer::Cfi => &["-flto", "-fvisibility=hidden"],
+        }
+    }
+
+    /// Runtime options variable and the defaults we want for fuzzing
+    fn runtime_options(&self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Sanitizer::Address => Some((
+                "ASAN_OPTIONS",
+                "abort_on_error=1:symbolize=1:detect_leaks=1",
+            )),
+            Sanitizer::Undefined => Some((
+                "UBSAN_OPTIONS",
+                "abort_on_error=1:halt_on_error=1:symbolize=1:print_stacktrace=1",
+            )),
+            Sanitizer::Memory => Some(("MSAN_OPTIONS", "abort_on_error=1:symbolize=1")),
+            Sanitizer::Thread => Some(("TSAN_OPTIONS", "abort_on_error=1:symbolize=1")),
+            Sanitizer::Leak => Some(("LSAN_OPTIONS", "symbolize=1:detect_leaks=1")),
+            Sanitizer::Cfi => None,
+        }
+    }
+}
+
+impl FromStr for Sanitizer {
+    type Err = String;
+
+    /// Accepts the `-fsanitize=` name or the short name, e.g. `address` or `asan`
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        const ALL: [Sanitizer; 6] = [
+            Sanitizer::Address,
+            Sanitizer::Undefined,
+            Sanitizer::Memory,
+            Sanitizer::Thread,
+            Sanitizer::Leak,
+            Sanitizer::Cfi,
+        ];
+        ALL.into_iter()
+            .find(|s| s.flag_name() == name || s.short_name() == name)
+            .ok_or_else(|| format!("unknown sanitizer `{}`", name))
+    }
+}
+
+/// Check that a set of sanitizers can be combined in one build
+pub fn validate_sanitizers(sanitizers: &[Sanitizer]) -> Result<(), CompileError> {
+    for (a, b) in INCOMPATIBLE {
+        if sanitizers.contains(a) && sanitizers.contains(b) {
+            return Err(CompileError::IncompatibleSanitizers {
+                first: *a,
+                second: *b,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Compiler flags enabling the given sanitizers
+pub fn sanitizer_flags(sanitizers: &[Sanitizer]) -> Vec<String> {
+    if sanitizers.is_empty() {
+        return vec![];
+    }
+
+    let mut sorted = sanitizers.to_vec();
+    sorted.sort();
+    sorted.dedup();
+
+    let names: Vec<&str> = sorted.iter().map(Sanitizer::flag_name).collect();
+    let mut flags = vec![format!("-fsanitize={}", names.join(","))];
+    for sanitizer in &sorted {
+        for flag in sanitizer.required_flags() {
+            if !flags.iter().any(|f| f == flag) {
+                flags.push(flag.to_string());
+            }
+        }
+    }
+    flags
+}
+
+/// Environment variables for running a binary built with the given sanitizers.
+///
+/// Options already present in the environment are appended after our defaults,
+/// so anything the user sets explicitly still wins.
+pub fn sanitizer_env(sanitizers: &[Sanitizer]) -> Vec<(String, String)> {
+    let mut env: Vec<(String, String)> = Vec::new();
+    for sanitizer in sanitizers {
+        let Some((var, defaults)) = sanitizer.runtime_options() else {
+            continue;
+        };
+        if env.iter().any(|(k, _)| k == var) {
+            continue;
+        }
+        let value = match std::env::var(var) {
+            Ok(user) if !user.is_empty() => format!("{}:{}", defaults, user),
+            _ => defaults.to_string(),
+        };
+        env.push((var.to_string(), value));
+    }
+    env
+}
+
+/// Suffix identifying a sanitizer variant, e.g. `asan_ubsan`
+pub fn variant_suffix(sanitizers: &[Sanitizer]) -> String {
+    if sanitizers.is_empty() {
+        return "plain".to_string();
+    }
+    let mut sorted = sanitizers.to_vec();
+    sorted.sort();
+    sorted.dedup();
+    sorted
+        .iter()
+        .map(Sanitizer::short_name)
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_incompatible_sanitizers() {
+        assert!(validate_sanitizers(&[Sanitizer::Address, Sanitizer::Undefined]).is_ok());
+        assert!(validate_sanitizers(&[Sanitizer::Address, Sanitizer::Leak]).is_ok());
+        assert!(validate_sanitizers(&[Sanitizer::Address, Sanitizer::Memory]).is_err());
+        assert!(validate_sanitizers(&[Sanitizer::Thread, Sanitizer::Leak]).is_err());
+    }
+
+    #[test]
+    fn test_parse_sanitizer() {
+        assert_eq!("address".parse(), Ok(Sanitizer::Address));
+        assert_eq!("ubsan".parse(), Ok(Sanitizer::Undefined));
+        assert!("valgrind".parse::<Sanitizer>().is_err());
+    }
+
+    #[test]
+    fn test_sanitizer_flags() {
+        let flags = sanitizer_flags(&[Sanitizer::Undefined, Sanitizer::Address, Sanitizer::Address]);
+        assert_eq!(flags[0], "-fsanitize=address,undefined");
+        assert!(flags.contains(&"-fno-omit-frame-pointer".to_string()));
+        assert!(sanitizer_flags(&[]).is_empty());
+        assert!(sanitizer_flags(&[Sanitizer::Cfi]).contains(&"-flto".to_string()));
+    }
+
+    #[test]
+    fn test_sanitizer_env() {
+        let env = sanitizer_env(&[Sanitizer::Address, Sanitizer::Undefined, Sanitizer::Cfi]);
+        let asan = env.iter().find(|(k, _)| k == "ASAN_OPTIONS").unwrap();
+        assert!(asan.1.starts_with("abort_on_error=1:symbolize=1:detect_leaks=1"));
+        assert!(env.iter().any(|(k, _)| k == "UBSAN_OPTIONS"));
+        assert_eq!(env.len(), 2);
+    }
+
+    #[test]
+    fn test_variant_suffix() {
+        assert_eq!(variant_suffix(&[]), "plain");
+        assert_eq!(variant_suffix(&[Sanitizer::Undefined, Sanitizer::Address]), "asan_ubsan");
+    }
+}
@@ -1,6 +1,14 @@
-use rand::Rng;
-use std::process::{Command, Output};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cell::{Cell, RefCell};
+use std::io::{self, Read, Write};
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::str::FromStr;
 use std::string::String;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::sanitizer::{sanitizer_env, Sanitizer};
 
 #[derive(Debug, PartialEq)]
 pub enum Outcome {
@@ -22,34 +30,234 @@ impl Runner for PrintRunner {
     }
 }
 
+/// How the fuzzed input reaches the program under test
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InputMode {
+    /// As the last command-line argument
+    #[default]
+    Argument,
+    /// On standard input
+    Stdin,
+    /// In a temporary file, whose path replaces `@@` in the arguments or is appended
+    File,
+}
+
+impl FromStr for InputMode {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "arg" | "argument" => Ok(InputMode::Argument),
+            "stdin" => Ok(InputMode::Stdin),
+            "file" => Ok(InputMode::File),
+            _ => Err(format!("unknown input mode `{}` (expected arg, stdin or file)", name)),
+        }
+    }
+}
+
+/// Result of running the program under test on one input
+#[derive(Debug)]
+pub struct Execution {
+    pub status: ExitStatus,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    /// Killed after running longer than the timeout
+    pub timed_out: bool,
+    pub duration: Duration,
+}
+
+impl Execution {
+    /// Killed by a signal, e.g. SIGSEGV or the SIGABRT of a sanitizer report
+    pub fn crashed(&self) -> bool {
+        !self.timed_out && self.status.code().is_none()
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        if self.status.success() {
+            Outcome::Pass
+        } else if self.crashed() {
+            Outcome::Fail
+        } else {
+            Outcome::Unresolved
+        }
+    }
+}
+
 pub struct ProgramRunner {
     program: String,
+    args: Vec<String>,
+    env: Vec<(String, String)>,
+    input_mode: InputMode,
+    timeout: Option<Duration>,
 }
 
 impl ProgramRunner {
     pub fn new(program: &str) -> Self {
         ProgramRunner {
             program: program.to_string(),
+            args: vec![],
+            env: vec![],
+            input_mode: InputMode::default(),
+            timeout: None,
         }
     }
 
-    fn run_process(&self, input: &str) -> std::io::Result<Output> {
-        Command::new(&self.program)
-            .arg(input)
-            .output()
+    /// Fixed arguments passed before the input (or around `@@` in file mode)
+    pub fn with_args(mut self, args: Vec<String>) -> Self {
+        self.args.extend(args);
+        self
+    }
+
+    pub fn with_input_mode(mut self, input_mode: InputMode) -> Self {
+        self.input_mode = input_mode;
+        self
+    }
+
+    /// Kill runs that take longer than this, reporting them as timed out
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set an environment variable for the program under test
+    pub fn with_env(mut self, key: &str, value: &str) -> Self {
+        self.env.retain(|(k, _)| k != key);
+        self.env.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Set up the runtime options for a binary built with these sanitizers
+    pub fn with_sanitizers(mut self, sanitizers: &[Sanitizer]) -> Self {
+        for (key, value) in sanitizer_env(sanitizers) {
+            self = self.with_env(&key, &value);
+        }
+        self
+    }
+
+    /// Run the program on one input, delivered according to the input mode. The bytes
+    /// reach the program as they are, whether or not they are UTF-8.
+    pub fn execute<I: AsRef<[u8]> + ?Sized>(&self, input: &I) -> io::Result<Execution> {
+        let input = input.as_ref();
+        let start = Instant::now();
+        let mut command = Command::new(&self.program);
+        // Its own process group, so that a timeout kills whatever it started too
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+        // Kept alive until the run is over
+        let mut input_file = None;
+
+        match self.input_mode {
+            InputMode::Argument => {
+                command.args(&self.args).arg(os_string(input)).stdin(Stdio::null());
+            }
+            InputMode::Stdin => {
+                command.args(&self.args).stdin(Stdio::piped());
+            }
+            InputMode::File => {
+                let mut file = tempfile::NamedTempFile::new()?;
+                file.write_all(input)?;
+                let path = file.path().to_string_lossy().into_owned();
+                if self.args.iter().any(|arg| arg == "@@") {
+                    command.args(self.args.iter().map(|arg| if arg == "@@" { &path } else { arg }));
+                } else {
+                    command.args(&self.args).arg(&path);
+                }
+                command.stdin(Stdio::null());
+                input_file = Some(file);
+            }
+        }
+
+        let mut child = command
+            .envs(self.env.iter().map(|(k, v)| (k, v)))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            let input = input.to_vec();
+            // A separate thread so a program that doesn't read its input can't block us
+            thread::spawn(move || {
+                let _ = stdin.write_all(&input);
+            });
+        }
+        let stdout = read_in_background(child.stdout.take());
+        let stderr = read_in_background(child.stderr.take());
+
+        let (status, timed_out) = self.wait(&mut child, start)?;
+        drop(input_file);
+
+        Ok(Execution {
+            status,
+            stdout: stdout.join().unwrap_or_default(),
+            stderr: stderr.join().unwrap_or_default(),
+            timed_out,
+            duration: start.elapsed(),
+        })
+    }
+
+    fn wait(&self, child: &mut Child, start: Instant) -> io::Result<(ExitStatus, bool)> {
+        let Some(timeout) = self.timeout else {
+            return Ok((child.wait()?, false));
+        };
+        loop {
+            if let Some(status) = child.try_wait()? {
+                return Ok((status, false));
+            }
+            if start.elapsed() >= timeout {
+                // The process may have exited since the check
+                kill_process_group(child);
+                return Ok((child.wait()?, true));
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
+
+/// Kill a child started by `execute` along with any processes it started, which could
+/// otherwise keep its output pipes open
+fn kill_process_group(child: &mut Child) {
+    #[cfg(unix)]
+    if let Ok(pid) = libc::pid_t::try_from(child.id()) {
+        // SAFETY: kill(2) has no memory safety requirements; the child is its own
+        // group leader and hasn't been reaped yet, so the group id is still ours
+        unsafe {
+            libc::kill(-pid, libc::SIGKILL);
+        }
+    }
+    let _ = child.kill();
+}
+
+/// Command-line argument holding exactly these bytes where the platform allows
+fn os_string(input: &[u8]) -> std::ffi::OsString {
+    #[cfg(unix)]
+    {
+        <std::ffi::OsStr as std::os::unix::ffi::OsStrExt>::from_bytes(input).to_os_string()
+    }
+    #[cfg(not(unix))]
+    {
+        String::from_utf8_lossy(input).into_owned().into()
     }
 }
 
+fn read_in_background<R: Read + Send + 'static>(pipe: Option<R>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buffer);
+        }
+        buffer
+    })
+}
+
 impl Runner for ProgramRunner {
     fn run(&self, input: &str) -> (Box<dyn std::any::Any>, Outcome) {
-        match self.run_process(input) {
-            Ok(output) => {
-                let outcome = if output.status.success() {
-                    Outcome::Pass
-                } else if output.status.code().is_none() {
-                    Outcome::Fail
-                } else {
-                    Outcome::Unresolved
+        match self.execute(input) {
+            Ok(execution) => {
+                let outcome = execution.outcome();
+                let output = Output {
+                    status: execution.status,
+                    stdout: execution.stdout,
+                    stderr: execution.stderr,
                 };
                 (Box::new(output), outcome)
             }
@@ -60,18 +268,36 @@ impl Runner for ProgramRunner {
 
 pub struct BinaryProgramRunner {
     program: String,
+    env: Vec<(String, String)>,
 }
 
 impl BinaryProgramRunner {
     pub fn new(program: &str) -> Self {
         BinaryProgramRunner {
             program: program.to_string(),
+            env: vec![],
         }
     }
 
+    /// Set an environment variable for the program under test
+    pub fn with_env(mut self, key: &str, value: &str) -> Self {
+        self.env.retain(|(k, _)| k != key);
+        self.env.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Set up the runtime options for a binary built with these sanitizers
+    pub fn with_sanitizers(mut self, sanitizers: &[Sanitizer]) -> Self {
+        for (key, value) in sanitizer_env(sanitizers) {
+            self = self.with_env(&key, &value);
+        }
+        self
+    }
+
     fn run_process(&self, input: &str) -> std::io::Result<Output> {
         Command::new(&self.program)
             .arg(input)
+            .envs(self.env.iter().map(|(k, v)| (k, v)))
             .output()
     }
 }
@@ -106,11 +332,29 @@ pub trait Fuzzer {
     }
 }
 
+/// Random number generator of a fuzzer, seeded explicitly so that a run can be
+/// replayed. Without a seed one is picked at random; `seed()` reports it.
+struct SeededRng {
+    seed: u64,
+    rng: RefCell<StdRng>,
+}
+
+impl SeededRng {
+    fn new(seed: Option<u64>) -> Self {
+        let seed = seed.unwrap_or_else(rand::random);
+        SeededRng {
+            seed,
+            rng: RefCell::new(StdRng::seed_from_u64(seed)),
+        }
+    }
+}
+
 pub struct RandomFuzzer {
     min_length: usize,
     max_length: usize,
     char_start: u32,
     char_range: u32,
+    rng: SeededRng,
 }
 
 impl RandomFuzzer {
@@ -120,24 +364,30 @@ impl RandomFuzzer {
             max_length,
             char_start,
             char_range,
+            rng: SeededRng::new(None),
         }
     }
+
+    /// Draw from an RNG seeded with `seed`, so the same seed gives the same inputs
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = SeededRng::new(Some(seed));
+        self
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.rng.seed
+    }
 }
 
 impl Default for RandomFuzzer {
     fn default() -> Self {
-        RandomFuzzer {
-            min_length: 10,
-            max_length: 100,
-            char_start: 32,
-            char_range: 32,
-        }
+        RandomFuzzer::new(10, 100, 32, 32)
     }
 }
 
 impl Fuzzer for RandomFuzzer {
     fn fuzz(&self) -> String {
-        let mut rng = rand::thread_rng();
+        let mut rng = self.rng.rng.borrow_mut();
         let string_length = rng.gen_range(self.min_length..=self.max_length);
         
         (0..string_length)
@@ -149,6 +399,137 @@ impl Fuzzer for RandomFuzzer {
     }
 }
 
+/// A single edit `MutationFuzzer` can make to an input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mutator {
+    /// Remove a random character
+    Delete,
+    /// Insert a random printable character
+    Insert,
+    /// Flip one of the low bits of a random character
+    Flip,
+}
+
+impl Mutator {
+    pub const ALL: [Mutator; 3] = [Mutator::Delete, Mutator::Insert, Mutator::Flip];
+}
+
+impl FromStr for Mutator {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "delete" => Ok(Mutator::Delete),
+            "insert" => Ok(Mutator::Insert),
+            "flip" => Ok(Mutator::Flip),
+            _ => Err(format!("unknown mutator `{}` (expected delete, insert or flip)", name)),
+        }
+    }
+}
+
+/// Mutates inputs from a growing population, starting with the seeds as they are.
+/// Inputs that find new coverage should be added back with `add_to_population`.
+pub struct MutationFuzzer {
+    seeds: Vec<String>,
+    population: Vec<String>,
+    min_mutations: usize,
+    max_mutations: usize,
+    mutators: Vec<Mutator>,
+    seed_index: Cell<usize>,
+    rng: SeededRng,
+}
+
+impl MutationFuzzer {
+    pub fn new(seeds: Vec<String>, min_mutations: usize, max_mutations: usize) -> Self {
+        MutationFuzzer {
+            population: seeds.clone(),
+            seeds,
+            min_mutations,
+            max_mutations,
+            mutators: Mutator::ALL.to_vec(),
+            seed_index: Cell::new(0),
+            rng: SeededRng::new(None),
+        }
+    }
+
+    /// Draw from an RNG seeded with `seed`, so the same seed and population give the
+    /// same mutations
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = SeededRng::new(Some(seed));
+        self
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.rng.seed
+    }
+
+    /// Restrict mutation to these mutators
+    pub fn with_mutators(mut self, mutators: Vec<Mutator>) -> Self {
+        self.mutators = mutators;
+        self
+    }
+
+    pub fn add_to_population(&mut self, input: String) {
+        self.population.push(input);
+    }
+
+    pub fn population(&self) -> &[String] {
+        &self.population
+    }
+
+    /// Apply one random mutation with one of the enabled mutators
+    pub fn mutate(&self, input: &str) -> String {
+        let mut rng = self.rng.rng.borrow_mut();
+        let mut chars: Vec<char> = input.chars().collect();
+        let Some(&mutator) = self.mutators.get(rng.gen_range(0..self.mutators.len().max(1))) else {
+            return input.to_string();
+        };
+        match mutator {
+            // Deleting from or flipping in an empty input leaves it empty
+            Mutator::Delete | Mutator::Flip if chars.is_empty() => {}
+            Mutator::Delete => {
+                chars.remove(rng.gen_range(0..chars.len()));
+            }
+            Mutator::Flip => {
+                let pos = rng.gen_range(0..chars.len());
+                let bit = 1u32 << rng.gen_range(0..7);
+                chars[pos] = char::from_u32(chars[pos] as u32 ^ bit).unwrap_or(chars[pos]);
+            }
+            Mutator::Insert => {
+                let pos = rng.gen_range(0..=chars.len());
+                chars.insert(pos, char::from(rng.gen_range(32u8..127)));
+            }
+        }
+        chars.into_iter().collect()
+    }
+}
+
+impl Fuzzer for MutationFuzzer {
+    fn fuzz(&self) -> String {
+        let index = self.seed_index.get();
+        if index < self.seeds.len() {
+            self.seed_index.set(index + 1);
+            return self.seeds[index].clone();
+        }
+
+        // `mutate` borrows the RNG again, so release it first
+        let (mut candidate, trials) = {
+            let mut rng = self.rng.rng.borrow_mut();
+            let candidate = if self.population.is_empty() {
+                String::new()
+            } else {
+                self.population[rng.gen_range(0..self.population.len())].clone()
+            };
+            let trials = rng.gen_range(self.min_mutations..=self.max_mutations.max(self.min_mutations));
+            (candidate, trials)
+        };
+        for _ in 0..trials {
+            candidate = self.mutate(&candidate);
+        }
+        candidate
+    }
+}
+
 /// # Examples
 /// 
 /// ## Using with the `cat` command:
@@ -191,6 +572,7 @@ impl Fuzzer for RandomFuzzer {
 /// 
 ///
 #[cfg(test)]
+#[allow(clippy::manual_range_contains, unused_variables)]
 mod tests {
     use super::*;
 
@@ -226,6 +608,106 @@ mod tests {
         assert_eq!(results.len(), 5);
     }
 
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn test_runner_sanitizer_env() {
+        use std::process::Output;
+
+        let runner = ProgramRunner::new("printenv").with_sanitizers(&[Sanitizer::Address]);
+        let (result, outcome) = runner.run("ASAN_OPTIONS");
+        assert_eq!(outcome, Outcome::Pass);
+        let output = result.downcast_ref::<Output>().unwrap();
+        assert!(String::from_utf8_lossy(&output.stdout).contains("abort_on_error=1"));
+    }
+
+    #[test]
+    fn test_mutation_fuzzer() {
+        let mut fuzzer = MutationFuzzer::new(vec!["seed".to_string()], 1, 3);
+        assert_eq!(fuzzer.fuzz(), "seed");
+
+        let mutated = fuzzer.fuzz();
+        assert!(mutated.chars().count().abs_diff(4) <= 3);
+        fuzzer.add_to_population("other".to_string());
+        assert_eq!(fuzzer.population().len(), 2);
+
+        // Only deletions, so inputs can only shrink
+        let deleting = MutationFuzzer::new(vec![], 1, 1).with_mutators(vec![Mutator::Delete]);
+        assert_eq!(deleting.mutate("abcd").len(), 3);
+        assert_eq!(deleting.mutate(""), "");
+        assert_eq!("flip".parse(), Ok(Mutator::Flip));
+    }
+
+    #[test]
+    fn test_seeded_fuzzers_replay() {
+        let sequence = |fuzzer: &dyn Fuzzer| (0..20).map(|_| fuzzer.fuzz()).collect::<Vec<_>>();
+
+        let random = |seed| RandomFuzzer::new(1, 30, 32, 95).with_seed(seed);
+        assert_eq!(random(42).seed(), 42);
+        assert_eq!(sequence(&random(42)), sequence(&random(42)));
+        assert_ne!(sequence(&random(42)), sequence(&random(43)));
+
+        let mutation = || MutationFuzzer::new(vec!["seed input".to_string()], 1, 5).with_seed(7);
+        assert_eq!(sequence(&mutation()), sequence(&mutation()));
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn test_input_modes() -> io::Result<()> {
+        let stdin = ProgramRunner::new("cat").with_input_mode(InputMode::Stdin);
+        assert_eq!(stdin.execute("from stdin")?.stdout, b"from stdin");
+
+        let file = ProgramRunner::new("cat").with_input_mode(InputMode::File);
+        assert_eq!(file.execute("from file")?.stdout, b"from file");
+
+        let argument = ProgramRunner::new("echo").with_args(vec!["-n".to_string()]);
+        assert_eq!(argument.execute("from arg")?.stdout, b"from arg");
+
+        assert_eq!("stdin".parse(), Ok(InputMode::Stdin));
+        assert!("socket".parse::<InputMode>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn test_timeout_and_crash() -> io::Result<()> {
+        let sleeper = ProgramRunner::new("sleep").with_timeout(Duration::from_millis(50));
+        let execution = sleeper.execute("5")?;
+        assert!(execution.timed_out);
+        assert!(!execution.crashed());
+        assert!(execution.duration < Duration::from_secs(5));
+
+        let crasher = ProgramRunner::new("sh").with_args(vec!["-c".to_string()]);
+        let execution = crasher.execute("kill -SEGV $$")?;
+        assert!(execution.crashed());
+        assert_eq!(execution.outcome(), Outcome::Fail);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn test_timeout_kills_child_processes() -> io::Result<()> {
+        // The shell's `sleep` holds the output pipes open after the shell is gone
+        let shell = ProgramRunner::new("sh")
+            .with_args(vec!["-c".to_string()])
+            .with_timeout(Duration::from_millis(50));
+        let execution = shell.execute("sleep 5; echo done")?;
+        assert!(execution.timed_out);
+        assert!(execution.duration < Duration::from_secs(5));
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn test_non_utf8_input() -> io::Result<()> {
+        let input = [b'a', 0xff, 0xfe, b'b'];
+        let stdin = ProgramRunner::new("cat").with_input_mode(InputMode::Stdin);
+        assert_eq!(stdin.execute(&input)?.stdout, input);
+
+        let argument = ProgramRunner::new("printf").with_args(vec!["%s".to_string()]);
+        assert_eq!(argument.execute(&input)?.stdout, input);
+        Ok(())
+    }
+
     #[test]
     #[cfg(target_family = "unix")]
     fn test_cat_program_runner() {
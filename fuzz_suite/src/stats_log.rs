@@ -0,0 +1,199 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use super::status::CampaignStats;
+
+/// Name of the stats file in a campaign's output directory
+pub const STATS_FILE: &str = "fuzzer_stats";
+
+/// One line of the `fuzzer_stats` file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatsRecord {
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub elapsed_secs: f64,
+    pub executions: u64,
+    pub execs_per_sec: f64,
+    pub line_coverage: f64,
+    pub branch_coverage: f64,
+    pub function_coverage: f64,
+    pub covered_lines: usize,
+    pub total_lines: usize,
+    pub covered_branches: usize,
+    pub total_branches: usize,
+    pub corpus_size: usize,
+    pub unique_crashes: usize,
+    pub hangs: usize,
+    /// Seed of the fuzzer's RNG; missing in files written before it was logged
+    #[serde(default)]
+    pub seed: Option<u64>,
+}
+
+impl StatsRecord {
+    pub fn new(stats: &CampaignStats) -> Self {
+        let coverage = &stats.coverage;
+        StatsRecord {
+            timestamp: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            elapsed_secs: stats.elapsed().as_secs_f64(),
+            executions: stats.executions,
+            execs_per_sec: stats.execs_per_sec(),
+            line_coverage: coverage.line_coverage(),
+            branch_coverage: coverage.branch_coverage(),
+            function_coverage: coverage.function_coverage(),
+            covered_lines: coverage.covered_lines,
+            total_lines: coverage.total_lines,
+            covered_branches: coverage.covered_branches,
+            total_branches: coverage.total_branches,
+            corpus_size: stats.corpus_size,
+            unique_crashes: stats.unique_crashes,
+            hangs: stats.hangs,
+            seed: stats.seed,
+        }
+    }
+}
+
+/// Appends a JSON-lines record of the campaign's progress to `fuzzer_stats` at a
+/// fixed interval, for graphing in other tools or with `plot_stats` afterwards
+pub struct StatsLog {
+    path: PathBuf,
+    interval: Duration,
+    /// None until the first record, which replaces the file
+    last_write: Option<Instant>,
+}
+
+impl StatsLog {
+    /// Log to `fuzzer_stats` in `output_dir`. The first record replaces whatever an
+    /// earlier campaign in the same directory logged, so the file holds one run.
+    pub fn new(output_dir: &Path) -> Self {
+        StatsLog {
+            path: output_dir.join(STATS_FILE),
+            interval: Duration::from_secs(1),
+            last_write: None,
+        }
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append a record if the interval has passed since the last one
+    pub fn record(&mut self, stats: &CampaignStats) -> io::Result<()> {
+        if self.last_write.is_some_and(|last| last.elapsed() < self.interval) {
+            return Ok(());
+        }
+        self.write(stats)
+    }
+
+    /// Append a record now, e.g. the final one of a campaign
+    pub fn write(&mut self, stats: &CampaignStats) -> io::Result<()> {
+        let first = self.last_write.replace(Instant::now()).is_none();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(!first)
+            .truncate(first)
+            .open(&self.path)?;
+        let line = serde_json::to_string(&StatsRecord::new(stats)).map_err(io::Error::other)?;
+        writeln!(file, "{}", line)
+    }
+}
+
+/// Read every record of a `fuzzer_stats` file
+pub fn read_stats(path: &Path) -> io::Result<Vec<StatsRecord>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut records = Vec::new();
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(&line).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid stats record on line {}: {}", number + 1, e),
+            )
+        })?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FileSummary;
+
+    #[test]
+    fn test_log_and_read() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut log = StatsLog::new(dir.path()).with_interval(Duration::from_secs(3600));
+        let mut stats = CampaignStats {
+            coverage: FileSummary {
+                total_lines: 10,
+                covered_lines: 4,
+                ..FileSummary::default()
+            },
+            seed: Some(7),
+            ..CampaignStats::default()
+        };
+
+        stats.executions = 10;
+        log.record(&stats)?;
+        // Within the interval, so skipped
+        stats.executions = 20;
+        log.record(&stats)?;
+        stats.executions = 30;
+        log.write(&stats)?;
+
+        let records = read_stats(&dir.path().join(STATS_FILE))?;
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].executions, 10);
+        assert_eq!(records[1].executions, 30);
+        assert_eq!(records[1].line_coverage, 40.0);
+        assert_eq!((records[1].covered_lines, records[1].total_lines), (4, 10));
+        assert_eq!(records[1].seed, Some(7));
+        Ok(())
+    }
+
+    #[test]
+    fn test_new_campaign_replaces_records() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut stats = CampaignStats::default();
+        let mut log = StatsLog::new(dir.path());
+        for executions in [10, 20] {
+            stats.executions = executions;
+            log.write(&stats)?;
+        }
+
+        // A second campaign in the same directory starts the file over
+        let mut log = StatsLog::new(dir.path());
+        stats.executions = 5;
+        log.write(&stats)?;
+        let records = read_stats(&dir.path().join(STATS_FILE))?;
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].executions, 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_record() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join(STATS_FILE);
+        std::fs::write(&path, "{\"executions\": 1}\n")?;
+        let err = read_stats(&path).unwrap_err();
+        assert!(err.to_string().contains("line 1"));
+        Ok(())
+    }
+}
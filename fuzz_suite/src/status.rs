@@ -0,0 +1,222 @@
+use std::fmt::Write as _;
+use std::io::{self, IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+use super::coverage::{percentage, FileSummary};
+
+/// Progress of a fuzzing campaign, as shown on the status screen
+#[derive(Debug, Clone)]
+pub struct CampaignStats {
+    pub start: Instant,
+    pub executions: u64,
+    pub corpus_size: usize,
+    /// Coverage totals over all counted files
+    pub coverage: FileSummary,
+    pub unique_crashes: usize,
+    pub hangs: usize,
+    /// When an input last increased coverage
+    pub last_new_path: Option<Instant>,
+    /// What the fuzzer is doing, e.g. the active mutator
+    pub stage: String,
+    /// Percentage of re-runs that reproduced the same coverage, if measured
+    pub stability: Option<f64>,
+    /// Seed of the fuzzer's RNG, to replay the campaign
+    pub seed: Option<u64>,
+}
+
+impl Default for CampaignStats {
+    fn default() -> Self {
+        CampaignStats {
+            start: Instant::now(),
+            executions: 0,
+            corpus_size: 0,
+            coverage: FileSummary::default(),
+            unique_crashes: 0,
+            hangs: 0,
+            last_new_path: None,
+            stage: String::new(),
+            stability: None,
+            seed: None,
+        }
+    }
+}
+
+impl CampaignStats {
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Average executions per second since the campaign started
+    pub fn execs_per_sec(&self) -> f64 {
+        let secs = self.elapsed().as_secs_f64();
+        if secs > 0.0 {
+            self.executions as f64 / secs
+        } else {
+            0.0
+        }
+    }
+}
+
+/// AFL-style status screen redrawn in place on a terminal. When stdout is not a
+/// terminal (CI logs, redirection) it prints one plain line per interval instead.
+pub struct StatusScreen {
+    tty: bool,
+    interval: Duration,
+    last_update: Option<Instant>,
+}
+
+impl Default for StatusScreen {
+    fn default() -> Self {
+        StatusScreen::new()
+    }
+}
+
+impl StatusScreen {
+    pub fn new() -> Self {
+        let tty = io::stdout().is_terminal();
+        StatusScreen {
+            tty,
+            interval: if tty { Duration::from_millis(250) } else { Duration::from_secs(5) },
+            last_update: None,
+        }
+    }
+
+    /// How often to redraw the screen, or to log a line when not on a terminal
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Show the stats if the refresh interval has passed since the last update
+    pub fn update(&mut self, stats: &CampaignStats) -> io::Result<()> {
+        if self.last_update.is_some_and(|last| last.elapsed() < self.interval) {
+            return Ok(());
+        }
+        self.draw(stats)
+    }
+
+    /// Show the stats now, e.g. once more when the campaign ends
+    pub fn draw(&mut self, stats: &CampaignStats) -> io::Result<()> {
+        self.last_update = Some(Instant::now());
+        let mut stdout = io::stdout().lock();
+        if self.tty {
+            // Move home and clear, then draw the whole screen
+            write!(stdout, "\x1b[H\x1b[2J{}", render_screen(stats))?;
+        } else {
+            writeln!(stdout, "{}", render_line(stats))?;
+        }
+        stdout.flush()
+    }
+}
+
+/// The full status screen
+pub fn render_screen(stats: &CampaignStats) -> String {
+    let coverage = &stats.coverage;
+    let rows = [
+        ("run time", format_duration(stats.elapsed())),
+        ("last new path", since(stats.last_new_path)),
+        ("total execs", stats.executions.to_string()),
+        ("exec speed", format!("{:.1}/sec", stats.execs_per_sec())),
+        ("corpus size", stats.corpus_size.to_string()),
+        ("unique crashes", stats.unique_crashes.to_string()),
+        ("hangs", stats.hangs.to_string()),
+        ("lines", ratio(coverage.covered_lines, coverage.total_lines)),
+        ("branches", ratio(coverage.covered_branches, coverage.total_branches)),
+        ("edges", ratio(coverage.covered_regions, coverage.total_regions)),
+        ("stage", if stats.stage.is_empty() { "-".to_string() } else { stats.stage.clone() }),
+        ("stability", stats.stability.map_or("n/a".to_string(), |s| format!("{:.2}%", s))),
+        ("seed", stats.seed.map_or("-".to_string(), |seed| seed.to_string())),
+    ];
+
+    let mut screen = String::from("+-------------------- fuzz_suite --------------------+\n");
+    for (label, value) in rows {
+        let _ = writeln!(screen, "| {:>15} : {:<32} |", label, value);
+    }
+    screen.push_str("+----------------------------------------------------+\n");
+    screen
+}
+
+/// One line summary for logs
+pub fn render_line(stats: &CampaignStats) -> String {
+    let coverage = &stats.coverage;
+    format!(
+        "[{}] execs: {} ({:.1}/s) corpus: {} lines: {} branches: {} edges: {} crashes: {} hangs: {} last new path: {} stage: {}",
+        format_duration(stats.elapsed()),
+        stats.executions,
+        stats.execs_per_sec(),
+        stats.corpus_size,
+        ratio(coverage.covered_lines, coverage.total_lines),
+        ratio(coverage.covered_branches, coverage.total_branches),
+        ratio(coverage.covered_regions, coverage.total_regions),
+        stats.unique_crashes,
+        stats.hangs,
+        since(stats.last_new_path),
+        if stats.stage.is_empty() { "-" } else { &stats.stage }
+    )
+}
+
+fn ratio(covered: usize, total: usize) -> String {
+    format!("{}/{} ({:.2}%)", covered, total, percentage(covered, total))
+}
+
+fn since(instant: Option<Instant>) -> String {
+    instant.map_or("none yet".to_string(), |instant| format!("{} ago", format_duration(instant.elapsed())))
+}
+
+/// `1h 02m 05s` style duration
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m {:02}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m {:02}s", secs / 3600, secs / 60 % 60, secs % 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats() -> CampaignStats {
+        CampaignStats {
+            executions: 1200,
+            corpus_size: 14,
+            coverage: FileSummary {
+                total_lines: 200,
+                covered_lines: 50,
+                total_branches: 40,
+                covered_branches: 10,
+                ..FileSummary::default()
+            },
+            unique_crashes: 2,
+            stage: "havoc".to_string(),
+            seed: Some(42),
+            ..CampaignStats::default()
+        }
+    }
+
+    #[test]
+    fn test_render_screen() {
+        let screen = render_screen(&stats());
+        assert!(screen.contains("|     total execs : 1200 "));
+        assert!(screen.contains("|           lines : 50/200 (25.00%) "));
+        assert!(screen.contains("|       stability : n/a "));
+        assert!(screen.contains("|            seed : 42 "));
+        assert!(screen.lines().all(|line| line.len() == screen.lines().next().unwrap().len()));
+    }
+
+    #[test]
+    fn test_render_line() {
+        let line = render_line(&stats());
+        assert!(line.contains("execs: 1200"));
+        assert!(line.contains("branches: 10/40 (25.00%)"));
+        assert!(line.contains("crashes: 2 hangs: 0 last new path: none yet stage: havoc"));
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_secs(42)), "42s");
+        assert_eq!(format_duration(Duration::from_secs(125)), "2m 05s");
+        assert_eq!(format_duration(Duration::from_secs(3725)), "1h 02m 05s");
+    }
+}
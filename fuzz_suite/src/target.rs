@@ -0,0 +1,637 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::Deserialize;
+use walkdir::WalkDir;
+
+use super::compiler::{compilation_failed, detect_language, CompilerConfig, Language};
+use super::error::CompileError;
+
+/// Name of the incremental build cache inside the build directory
+const CACHE_FILE: &str = "build_cache";
+
+/// One source file of a target, with any flags specific to it
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranslationUnit {
+    pub source: PathBuf,
+    /// Directory the compiler is run from (relative flags are resolved against it)
+    pub directory: Option<PathBuf>,
+    pub flags: Vec<String>,
+}
+
+impl TranslationUnit {
+    pub fn new(source: &Path) -> Self {
+        TranslationUnit {
+            source: source.to_path_buf(),
+            directory: None,
+            flags: vec![],
+        }
+    }
+}
+
+/// A program made of several translation units
+#[derive(Debug, Clone, Default)]
+pub struct BuildTarget {
+    pub units: Vec<TranslationUnit>,
+    pub include_dirs: Vec<PathBuf>,
+    pub defines: Vec<String>,
+    pub lib_dirs: Vec<PathBuf>,
+    pub link_libs: Vec<String>,
+}
+
+/// What an incremental build actually did
+#[derive(Debug, Clone, Default)]
+pub struct BuildOutcome {
+    pub compiled: Vec<PathBuf>,
+    pub up_to_date: Vec<PathBuf>,
+    pub linked: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct CompileCommand {
+    directory: PathBuf,
+    file: PathBuf,
+    #[serde(default)]
+    arguments: Option<Vec<String>>,
+    #[serde(default)]
+    command: Option<String>,
+}
+
+impl BuildTarget {
+    pub fn new() -> Self {
+        BuildTarget::default()
+    }
+
+    /// Collect every C/C++ source file below a directory
+    pub fn from_dir(dir: &Path) -> io::Result<Self> {
+        let mut sources: Vec<PathBuf> = WalkDir::new(dir)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| e.file_type().is_file() && detect_language(e.path()).is_some())
+            .map(|e| e.path().to_path_buf())
+            .collect();
+        sources.sort();
+
+        if sources.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No C/C++ sources found in {:?}", dir),
+            ));
+        }
+
+        Ok(BuildTarget::new()
+            .with_sources(sources)
+            .with_include_dirs(vec![dir.to_path_buf()]))
+    }
+
+    /// Import the translation units from a `compile_commands.json`
+    pub fn from_compile_commands(path: &Path) -> io::Result<Self> {
+        let content = fs::read_to_string(path)?;
+        let commands: Vec<CompileCommand> = serde_json::from_str(&content)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let mut target = BuildTarget::new();
+        for command in commands {
+            let args = match (command.arguments, command.command) {
+                (Some(args), _) => args,
+                (None, Some(cmd)) => split_command_line(&cmd),
+                (None, None) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("No command for {:?} in {:?}", command.file, path),
+                    ))
+                }
+            };
+
+            let source = if command.file.is_absolute() {
+                command.file.clone()
+            } else {
+                command.directory.join(&command.file)
+            };
+            if target.units.iter().any(|u| u.source == source) {
+                continue;
+            }
+
+            target.units.push(TranslationUnit {
+                flags: unit_flags(&args, &command.file),
+                source,
+                directory: Some(command.directory),
+            });
+        }
+        Ok(target)
+    }
+
+    pub fn with_sources(mut self, sources: Vec<PathBuf>) -> Self {
+        self.units
+            .extend(sources.iter().map(|s| TranslationUnit::new(s)));
+        self
+    }
+
+    pub fn with_include_dirs(mut self, dirs: Vec<PathBuf>) -> Self {
+        self.include_dirs.extend(dirs);
+        self
+    }
+
+    /// Add preprocessor defines, written as `NAME` or `NAME=VALUE`
+    pub fn with_defines(mut self, defines: Vec<String>) -> Self {
+        self.defines.extend(defines);
+        self
+    }
+
+    pub fn with_lib_dirs(mut self, dirs: Vec<PathBuf>) -> Self {
+        self.lib_dirs.extend(dirs);
+        self
+    }
+
+    /// Add libraries to link, written without the `-l` prefix
+    pub fn with_link_libs(mut self, libs: Vec<String>) -> Self {
+        self.link_libs.extend(libs);
+        self
+    }
+
+    fn has_cpp(&self) -> bool {
+        self.units
+            .iter()
+            .any(|u| detect_language(&u.source) == Some(Language::Cpp))
+    }
+
+    /// Compile every translation unit with instrumentation and link them into `output_file`.
+    ///
+    /// Objects and the build cache live in `build_dir`; a unit is only recompiled when
+    /// the hash of its source, the headers it included last time or its flags changed.
+    pub fn build(
+        &self,
+        compiler: &CompilerConfig,
+        build_dir: &Path,
+        output_file: &Path,
+    ) -> Result<BuildOutcome, CompileError> {
+        if self.units.is_empty() {
+            return Err(CompileError::InvalidConfig(
+                "build target has no sources".to_string(),
+            ));
+        }
+        compiler.validate()?;
+
+        // Units may be compiled from their own directory, so every path we hand the
+        // compiler has to be absolute
+        let obj_dir = std::path::absolute(build_dir)?.join("obj");
+        fs::create_dir_all(&obj_dir)?;
+        let cache_path = build_dir.join(CACHE_FILE);
+        let mut cache = load_cache(&cache_path);
+        let mut outcome = BuildOutcome::default();
+        let mut objects = Vec::new();
+
+        for unit in &self.units {
+            let object = obj_dir.join(object_name(&unit.source));
+            let depfile = object.with_extension("d");
+            let args = self.compile_args(compiler, unit, &object, &depfile);
+            let key = object.to_string_lossy().into_owned();
+
+            let up_to_date = object.exists()
+                && cache.get(&key) == Some(&unit_hash(unit, &args, &depfile));
+            if up_to_date {
+                outcome.up_to_date.push(unit.source.clone());
+            } else {
+                let mut command = Command::new(&compiler.compiler_path);
+                command.args(&args);
+                if let Some(dir) = &unit.directory {
+                    command.current_dir(dir);
+                }
+                let output = command
+                    .output()
+                    .map_err(|e| CompileError::from_spawn(&compiler.compiler_path, e))?;
+                if !output.status.success() {
+                    cache.remove(&key);
+                    save_cache(&cache_path, &cache)?;
+                    return Err(compilation_failed(&unit.source, &output));
+                }
+                cache.insert(key, unit_hash(unit, &args, &depfile));
+                outcome.compiled.push(unit.source.clone());
+            }
+            objects.push(object);
+        }
+
+        let link_args = self.link_args(compiler, &objects, output_file);
+        let link_key = format!("link:{}", output_file.display());
+        let mut link_hash = Fnv::new();
+        for arg in &link_args {
+            link_hash.write(arg.as_bytes());
+        }
+        let link_hash = link_hash.finish_hex();
+
+        if !outcome.compiled.is_empty()
+            || !output_file.exists()
+            || cache.get(&link_key) != Some(&link_hash)
+        {
+            let linker = if self.has_cpp() {
+                compiler.cxx_driver()
+            } else {
+                compiler.compiler_path.clone()
+            };
+            let output = Command::new(&linker)
+                .args(&link_args)
+                .output()
+                .map_err(|e| CompileError::from_spawn(&linker, e))?;
+            if !output.status.success() {
+                save_cache(&cache_path, &cache)?;
+                return Err(compilation_failed(output_file, &output));
+            }
+            cache.insert(link_key, link_hash);
+            outcome.linked = true;
+        }
+
+        save_cache(&cache_path, &cache)?;
+        Ok(outcome)
+    }
+
+    fn compile_args(
+        &self,
+        compiler: &CompilerConfig,
+        unit: &TranslationUnit,
+        object: &Path,
+        depfile: &Path,
+    ) -> Vec<String> {
+        let mut args = vec![
+            "-c".to_string(),
+            unit.source.to_string_lossy().into_owned(),
+            "-o".to_string(),
+            object.to_string_lossy().into_owned(),
+            "-MMD".to_string(),
+            "-MF".to_string(),
+            depfile.to_string_lossy().into_owned(),
+            compiler.optimization_level.clone(),
+        ];
+        args.extend(compiler.instrumentation_flags());
+        args.extend(self.source_flags(unit));
+        args.extend(compiler.extra_flags.iter().cloned());
+        args
+    }
+
+    /// Include directories, defines and the unit's own flags: what any compilation of
+    /// the unit needs besides the compiler's flags
+    pub(crate) fn source_flags(&self, unit: &TranslationUnit) -> Vec<String> {
+        let mut flags: Vec<String> = self
+            .include_dirs
+            .iter()
+            .map(|d| {
+                let dir = std::path::absolute(d).unwrap_or_else(|_| d.clone());
+                format!("-I{}", dir.display())
+            })
+            .collect();
+        flags.extend(self.defines.iter().map(|d| format!("-D{}", d)));
+        flags.extend(unit.flags.iter().cloned());
+        flags
+    }
+
+    fn link_args(
+        &self,
+        compiler: &CompilerConfig,
+        objects: &[PathBuf],
+        output_file: &Path,
+    ) -> Vec<String> {
+        let mut args: Vec<String> = objects
+            .iter()
+            .map(|o| o.to_string_lossy().into_owned())
+            .collect();
+        args.push("-o".to_string());
+        args.push(output_file.to_string_lossy().into_owned());
+        args.extend(compiler.instrumentation_flags());
+        args.extend(compiler.extra_flags.iter().cloned());
+        args.extend(self.lib_dirs.iter().map(|d| format!("-L{}", d.display())));
+        args.extend(self.link_libs.iter().map(|l| format!("-l{}", l)));
+        args
+    }
+}
+
+/// Keep the flags of a compile command that affect how the unit is compiled, dropping
+/// the compiler itself, the input, the output and the optimization level.
+fn unit_flags(args: &[String], file: &Path) -> Vec<String> {
+    let file = file.to_string_lossy();
+    let mut flags = Vec::new();
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-c" => {}
+            "-o" | "-MF" | "-MT" | "-MQ" => {
+                iter.next();
+            }
+            "-MD" | "-MMD" => {}
+            a if a == file => {}
+            a if a.starts_with("-O") || a.starts_with("-o") => {}
+            a => flags.push(a.to_string()),
+        }
+    }
+    flags
+}
+
+/// Split a shell command line into words, honouring quotes and backslash escapes
+fn split_command_line(command: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+    let mut chars = command.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') | (None, '\\') => {
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                    in_word = true;
+                }
+            }
+            (Some(_), c) => current.push(c),
+            (None, '"') | (None, '\'') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(current);
+    }
+    words
+}
+
+/// Object file name that stays unique for sources with the same file name
+fn object_name(source: &Path) -> String {
+    let stem = source
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("unit");
+    let mut hash = Fnv::new();
+    hash.write(source.to_string_lossy().as_bytes());
+    format!("{}-{}.o", stem, hash.finish_hex())
+}
+
+/// Files listed in a make-style dependency file written by `-MMD`
+fn parse_depfile(content: &str) -> Vec<PathBuf> {
+    let joined = content.replace("\\\r\n", " ").replace("\\\n", " ");
+    let Some((_, deps)) = joined.split_once(": ") else {
+        return vec![];
+    };
+
+    // Spaces in file names are written as `\ `
+    let mut files = Vec::new();
+    let mut current = String::new();
+    let mut chars = deps.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&' ') => {
+                current.push(' ');
+                chars.next();
+            }
+            c if c.is_whitespace() => {
+                if !current.is_empty() {
+                    files.push(PathBuf::from(std::mem::take(&mut current)));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        files.push(PathBuf::from(current));
+    }
+    files
+}
+
+fn unit_hash(unit: &TranslationUnit, args: &[String], depfile: &Path) -> String {
+    let mut hash = Fnv::new();
+    for arg in args {
+        hash.write(arg.as_bytes());
+        hash.write(&[0]);
+    }
+    hash.write(&fs::read(&unit.source).unwrap_or_default());
+
+    // Headers recorded by the previous compilation, relative to the directory the unit
+    // was compiled from
+    if let Ok(content) = fs::read_to_string(depfile) {
+        for dep in parse_depfile(&content) {
+            let path = match &unit.directory {
+                Some(dir) if dep.is_relative() => dir.join(&dep),
+                _ => dep.clone(),
+            };
+            hash.write(dep.to_string_lossy().as_bytes());
+            hash.write(&fs::read(&path).unwrap_or_default());
+        }
+    }
+    hash.finish_hex()
+}
+
+fn load_cache(path: &Path) -> HashMap<String, String> {
+    fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.rsplit_once('\t'))
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+fn save_cache(path: &Path, cache: &HashMap<String, String>) -> io::Result<()> {
+    let mut entries: Vec<_> = cache.iter().collect();
+    entries.sort();
+    let content: String = entries
+        .into_iter()
+        .map(|(k, v)| format!("{}\t{}\n", k, v))
+        .collect();
+    fs::write(path, content)
+}
+
+/// FNV-1a, used because its output is stable across Rust versions
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Fnv(0xcbf29ce484222325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn finish_hex(&self) -> String {
+        format!("{:016x}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_split_command_line() {
+        assert_eq!(
+            split_command_line(r#"clang -DNAME="a b" -I 'inc dir' -c x.c"#),
+            vec!["clang", "-DNAME=a b", "-I", "inc dir", "-c", "x.c"]
+        );
+        assert_eq!(split_command_line(r"cc a\ b.c"), vec!["cc", "a b.c"]);
+    }
+
+    #[test]
+    fn test_from_compile_commands() -> io::Result<()> {
+        let dir = tempdir()?;
+        let db = dir.path().join("compile_commands.json");
+        fs::write(
+            &db,
+            r#"[
+                {"directory": "/proj", "file": "src/a.c",
+                 "arguments": ["cc", "-Iinclude", "-DX=1", "-O2", "-c", "src/a.c", "-o", "a.o"]},
+                {"directory": "/proj", "file": "/proj/src/b.cpp",
+                 "command": "c++ -std=c++17 -c /proj/src/b.cpp -o b.o"}
+            ]"#,
+        )?;
+
+        let target = BuildTarget::from_compile_commands(&db)?;
+        assert_eq!(target.units.len(), 2);
+        assert_eq!(target.units[0].source, PathBuf::from("/proj/src/a.c"));
+        assert_eq!(target.units[0].flags, vec!["-Iinclude", "-DX=1"]);
+        assert_eq!(target.units[0].directory, Some(PathBuf::from("/proj")));
+        assert_eq!(target.units[1].flags, vec!["-std=c++17"]);
+        assert!(target.has_cpp());
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_dir() -> io::Result<()> {
+        let dir = tempdir()?;
+        fs::create_dir_all(dir.path().join("sub"))?;
+        fs::write(dir.path().join("main.c"), "int main() { return 0; }")?;
+        fs::write(dir.path().join("sub/util.c"), "")?;
+        fs::write(dir.path().join("sub/util.h"), "")?;
+
+        let target = BuildTarget::from_dir(dir.path())?;
+        assert_eq!(target.units.len(), 2);
+        assert!(!target.has_cpp());
+        assert!(BuildTarget::from_dir(&dir.path().join("sub/none")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unit_hash_tracks_headers() -> io::Result<()> {
+        let dir = tempdir()?;
+        let source = dir.path().join("a.c");
+        let header = dir.path().join("a.h");
+        let depfile = dir.path().join("a.d");
+        fs::write(&source, "#include \"a.h\"")?;
+        fs::write(&header, "int x;")?;
+        fs::write(
+            &depfile,
+            format!("a.o: {} \\\n  {}\n", source.display(), header.display()),
+        )?;
+
+        let unit = TranslationUnit::new(&source);
+        let args = vec!["-O0".to_string()];
+        let before = unit_hash(&unit, &args, &depfile);
+        assert_eq!(before, unit_hash(&unit, &args, &depfile));
+
+        fs::write(&header, "int y;")?;
+        assert_ne!(before, unit_hash(&unit, &args, &depfile));
+        assert_ne!(
+            unit_hash(&unit, &args, &depfile),
+            unit_hash(&unit, &["-O1".to_string()], &depfile)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_depfile() {
+        let deps = parse_depfile("obj/a.o: src/a.c \\\n  include/my\\ header.h b.h\n");
+        assert_eq!(
+            deps,
+            vec![
+                PathBuf::from("src/a.c"),
+                PathBuf::from("include/my header.h"),
+                PathBuf::from("b.h")
+            ]
+        );
+        assert!(parse_depfile("").is_empty());
+    }
+
+    #[test]
+    fn test_unit_hash_relative_headers() -> io::Result<()> {
+        // Built from compile_commands.json in another directory
+        let dir = tempdir()?;
+        let source = dir.path().join("a.c");
+        let depfile = dir.path().join("a.d");
+        fs::write(&source, "#include \"a.h\"")?;
+        fs::write(dir.path().join("a.h"), "int x;")?;
+        fs::write(&depfile, "a.o: a.c a.h\n")?;
+
+        let unit = TranslationUnit {
+            directory: Some(dir.path().to_path_buf()),
+            ..TranslationUnit::new(&source)
+        };
+        let before = unit_hash(&unit, &[], &depfile);
+        fs::write(dir.path().join("a.h"), "int y;")?;
+        assert_ne!(before, unit_hash(&unit, &[], &depfile));
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn test_incremental_build() -> Result<(), CompileError> {
+        use std::os::unix::fs::PermissionsExt;
+
+        // Stand-in compiler that just creates whatever follows `-o`
+        let dir = tempdir()?;
+        let fake_cc = dir.path().join("fake-cc");
+        fs::write(
+            &fake_cc,
+            "#!/bin/sh\nwhile [ $# -gt 0 ]; do\n  if [ \"$1\" = \"-o\" ]; then touch \"$2\"; fi\n  shift\ndone\n",
+        )?;
+        fs::set_permissions(&fake_cc, fs::Permissions::from_mode(0o755))?;
+
+        let a = dir.path().join("a.c");
+        let b = dir.path().join("b.c");
+        fs::write(&a, "int a;")?;
+        fs::write(&b, "int b;")?;
+
+        let mut compiler = CompilerConfig::new(Language::C);
+        compiler.compiler_path = fake_cc.to_string_lossy().into_owned();
+        let target = BuildTarget::new().with_sources(vec![a.clone(), b.clone()]);
+        let build_dir = dir.path().join("build");
+        let output = dir.path().join("prog");
+
+        let first = target.build(&compiler, &build_dir, &output)?;
+        assert_eq!(first.compiled.len(), 2);
+        assert!(first.linked);
+
+        let second = target.build(&compiler, &build_dir, &output)?;
+        assert!(second.compiled.is_empty());
+        assert!(!second.linked);
+
+        fs::write(&b, "int b = 1;")?;
+        let third = target.build(&compiler, &build_dir, &output)?;
+        assert_eq!(third.compiled, vec![b]);
+        assert_eq!(third.up_to_date, vec![a]);
+        assert!(third.linked);
+        Ok(())
+    }
+
+    #[test]
+    fn test_object_names_unique() {
+        assert_ne!(
+            object_name(Path::new("a/util.c")),
+            object_name(Path::new("b/util.c"))
+        );
+        assert!(object_name(Path::new("a/util.c")).starts_with("util-"));
+    }
+}
@@ -0,0 +1,279 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::error::CompileError;
+
+/// Matching clang and LLVM coverage tools.
+///
+/// The profile format written by an instrumented binary is tied to the clang that built
+/// it, so `llvm-profdata` and `llvm-cov` have to come from the same LLVM release.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Toolchain {
+    pub clang: PathBuf,
+    pub clangxx: PathBuf,
+    pub llvm_profdata: PathBuf,
+    pub llvm_cov: PathBuf,
+    /// LLVM major version, when known
+    pub version: Option<u32>,
+}
+
+impl Default for Toolchain {
+    fn default() -> Self {
+        Toolchain::with_suffix(Path::new(""), "")
+    }
+}
+
+impl Toolchain {
+    fn with_suffix(bin_dir: &Path, suffix: &str) -> Self {
+        Toolchain {
+            clang: bin_dir.join(format!("clang{}", suffix)),
+            clangxx: bin_dir.join(format!("clang++{}", suffix)),
+            llvm_profdata: bin_dir.join(format!("llvm-profdata{}", suffix)),
+            llvm_cov: bin_dir.join(format!("llvm-cov{}", suffix)),
+            version: None,
+        }
+    }
+
+    /// Find a matching toolchain, preferring `LLVM_CONFIG` when it is set and otherwise
+    /// searching `PATH` for unversioned tools and then `clang-N`/`llvm-cov-N` style names
+    pub fn discover() -> Result<Self, CompileError> {
+        if let Some(llvm_config) = std::env::var_os("LLVM_CONFIG") {
+            return Toolchain::from_llvm_config(Path::new(&llvm_config));
+        }
+        let path = std::env::var_os("PATH").unwrap_or_default();
+        Toolchain::discover_in(&path)
+    }
+
+    /// Use the tools from the bin directory reported by `llvm-config`
+    pub fn from_llvm_config(llvm_config: &Path) -> Result<Self, CompileError> {
+        let tool = llvm_config.to_string_lossy().into_owned();
+        let output = Command::new(llvm_config)
+            .arg("--bindir")
+            .output()
+            .map_err(|e| CompileError::from_tool_spawn(&tool, e))?;
+        if !output.status.success() {
+            return Err(CompileError::InvalidConfig(format!(
+                "`{} --bindir` failed",
+                tool
+            )));
+        }
+
+        let bin_dir = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+        let mut toolchain = Toolchain::with_suffix(&bin_dir, "");
+        for path in toolchain.tools() {
+            if !path.is_file() {
+                return Err(CompileError::LlvmToolNotFound {
+                    tool: path.to_string_lossy().into_owned(),
+                });
+            }
+        }
+        toolchain.version = toolchain.check_versions()?;
+        Ok(toolchain)
+    }
+
+    fn discover_in(search_path: &OsStr) -> Result<Self, CompileError> {
+        let dirs: Vec<PathBuf> = std::env::split_paths(search_path).collect();
+
+        // Unversioned names first, then the newest versioned set
+        let mut suffixes = vec![String::new()];
+        let mut versions = installed_versions(&dirs);
+        versions.sort_unstable_by(|a, b| b.cmp(a));
+        suffixes.extend(versions.iter().map(|v| format!("-{}", v)));
+
+        let mut last_error = None;
+        for suffix in &suffixes {
+            let names = Toolchain::with_suffix(Path::new(""), suffix);
+            let resolved: Option<Vec<PathBuf>> = names
+                .tools()
+                .iter()
+                .map(|name| find_in_dirs(&dirs, name))
+                .collect();
+            let Some(resolved) = resolved else {
+                continue;
+            };
+
+            let mut toolchain = Toolchain {
+                clang: resolved[0].clone(),
+                clangxx: resolved[1].clone(),
+                llvm_profdata: resolved[2].clone(),
+                llvm_cov: resolved[3].clone(),
+                version: None,
+            };
+            match toolchain.check_versions() {
+                Ok(version) => {
+                    toolchain.version = version;
+                    return Ok(toolchain);
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| {
+            let missing = ["clang", "clang++", "llvm-profdata", "llvm-cov"]
+                .into_iter()
+                .find(|name| find_in_dirs(&dirs, Path::new(name)).is_none())
+                .unwrap_or("clang");
+            CompileError::LlvmToolNotFound {
+                tool: missing.to_string(),
+            }
+        }))
+    }
+
+    fn tools(&self) -> [&Path; 4] {
+        [&self.clang, &self.clangxx, &self.llvm_profdata, &self.llvm_cov]
+    }
+
+    /// Check that every tool reports the same LLVM major version and return it.
+    /// Tools whose version can't be read (e.g. Apple's renumbered clang) are skipped.
+    pub fn check_versions(&self) -> Result<Option<u32>, CompileError> {
+        let mut found: Option<(String, u32)> = None;
+        for tool in self.tools() {
+            let name = tool.to_string_lossy().into_owned();
+            let Some(version) = tool_version(tool)? else {
+                continue;
+            };
+            match &found {
+                Some((first, expected)) if *expected != version => {
+                    return Err(CompileError::ToolchainMismatch {
+                        first: first.clone(),
+                        expected: *expected,
+                        tool: name,
+                        found: version,
+                    });
+                }
+                Some(_) => {}
+                None => found = Some((name, version)),
+            }
+        }
+        Ok(found.map(|(_, version)| version))
+    }
+}
+
+/// Major version printed by `<tool> --version`
+fn tool_version(tool: &Path) -> Result<Option<u32>, CompileError> {
+    let output = Command::new(tool)
+        .arg("--version")
+        .output()
+        .map_err(|e| CompileError::from_tool_spawn(&tool.to_string_lossy(), e))?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    Ok(parse_version(&text))
+}
+
+/// Parse `clang version 17.0.6` or `LLVM version 14.0.6`
+fn parse_version(text: &str) -> Option<u32> {
+    text.lines()
+        .filter(|line| !line.contains("Apple"))
+        .find_map(|line| {
+            let rest = &line[line.find("version ")? + "version ".len()..];
+            rest.split(|c: char| !c.is_ascii_digit()).next()?.parse().ok()
+        })
+}
+
+/// Versions N for which a `clang-N` exists in one of the directories
+fn installed_versions(dirs: &[PathBuf]) -> Vec<u32> {
+    let mut versions = Vec::new();
+    for dir in dirs {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in entries.filter_map(Result::ok) {
+            let name = entry.file_name();
+            let Some(version) = name
+                .to_str()
+                .and_then(|n| n.strip_prefix("clang-"))
+                .and_then(|v| v.parse::<u32>().ok())
+            else {
+                continue;
+            };
+            if !versions.contains(&version) {
+                versions.push(version);
+            }
+        }
+    }
+    versions
+}
+
+fn find_in_dirs(dirs: &[PathBuf], name: &Path) -> Option<PathBuf> {
+    dirs.iter().map(|dir| dir.join(name)).find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(parse_version("Ubuntu clang version 17.0.6 (++20231209)\nTarget: x86_64"), Some(17));
+        assert_eq!(parse_version("LLVM (http://llvm.org/):\n  LLVM version 14.0.6\n"), Some(14));
+        assert_eq!(parse_version("Apple clang version 15.0.0 (clang-1500.1.0.2.5)"), None);
+        assert_eq!(parse_version("garbage"), None);
+    }
+
+    #[cfg(target_family = "unix")]
+    fn fake_tool(dir: &Path, name: &str, version_line: &str) {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = dir.join(name);
+        fs::write(&path, format!("#!/bin/sh\necho '{}'\n", version_line)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn test_discover_prefers_matching_versions() {
+        let dir = tempdir().unwrap();
+        // Unversioned tools from different releases must be skipped
+        fake_tool(dir.path(), "clang", "clang version 18.1.0");
+        fake_tool(dir.path(), "clang++", "clang version 18.1.0");
+        fake_tool(dir.path(), "llvm-profdata", "LLVM version 18.1.0");
+        fake_tool(dir.path(), "llvm-cov", "LLVM version 14.0.6");
+        for version in ["14", "17"] {
+            fake_tool(dir.path(), &format!("clang-{}", version), &format!("clang version {}.0.1", version));
+            fake_tool(dir.path(), &format!("clang++-{}", version), &format!("clang version {}.0.1", version));
+            fake_tool(dir.path(), &format!("llvm-profdata-{}", version), &format!("LLVM version {}.0.1", version));
+            fake_tool(dir.path(), &format!("llvm-cov-{}", version), &format!("LLVM version {}.0.1", version));
+        }
+
+        let toolchain = Toolchain::discover_in(dir.path().as_os_str()).unwrap();
+        assert_eq!(toolchain.version, Some(17));
+        assert_eq!(toolchain.clang, dir.path().join("clang-17"));
+        assert_eq!(toolchain.llvm_cov, dir.path().join("llvm-cov-17"));
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn test_discover_reports_mismatch_and_missing() {
+        let dir = tempdir().unwrap();
+        fake_tool(dir.path(), "clang", "clang version 18.1.0");
+        fake_tool(dir.path(), "clang++", "clang version 18.1.0");
+        fake_tool(dir.path(), "llvm-profdata", "LLVM version 18.1.0");
+        fake_tool(dir.path(), "llvm-cov", "LLVM version 14.0.6");
+
+        let err = Toolchain::discover_in(dir.path().as_os_str()).unwrap_err();
+        assert!(matches!(err, CompileError::ToolchainMismatch { expected: 18, found: 14, .. }));
+
+        let empty = tempdir().unwrap();
+        let err = Toolchain::discover_in(empty.path().as_os_str()).unwrap_err();
+        assert!(matches!(err, CompileError::LlvmToolNotFound { ref tool } if tool == "clang"));
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn test_from_llvm_config() {
+        let dir = tempdir().unwrap();
+        let bin = dir.path().join("bin");
+        fs::create_dir_all(&bin).unwrap();
+        fake_tool(&bin, "clang", "clang version 16.0.0");
+        fake_tool(&bin, "clang++", "clang version 16.0.0");
+        fake_tool(&bin, "llvm-profdata", "LLVM version 16.0.0");
+        fake_tool(&bin, "llvm-cov", "LLVM version 16.0.0");
+        fake_tool(dir.path(), "llvm-config", &bin.to_string_lossy());
+
+        let toolchain = Toolchain::from_llvm_config(&dir.path().join("llvm-config")).unwrap();
+        assert_eq!(toolchain.version, Some(16));
+        assert_eq!(toolchain.llvm_profdata, bin.join("llvm-profdata"));
+    }
+}
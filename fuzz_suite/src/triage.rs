@@ -0,0 +1,121 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Frames of the sanitizer runtime itself, which say nothing about the bug
+const RUNTIME_FRAMES: &[&str] = &["__asan", "__ubsan", "__msan", "__tsan", "__lsan", "__sanitizer", "__interceptor"];
+
+/// Number of stack frames that identify a crash
+const SIGNATURE_FRAMES: usize = 3;
+
+/// Signature of a crash with no sanitizer report to tell it apart from others
+pub(crate) const GENERIC_SIGNATURE: &str = "crash";
+
+/// Crashing inputs that share a signature, most likely the same bug
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrashGroup {
+    pub signature: String,
+    pub inputs: Vec<PathBuf>,
+}
+
+/// Identify a crash by its kind and the innermost frames of the target, from the
+/// sanitizer report on stderr, e.g.
+/// `AddressSanitizer: heap-buffer-overflow in decode < main`
+pub fn crash_signature(stderr: &str) -> String {
+    let kind = stderr
+        .lines()
+        .find_map(|line| {
+            if let Some((_, report)) = line.split_once("ERROR: ") {
+                // `AddressSanitizer: heap-buffer-overflow on address 0x...`
+                let report = report.split(" on ").next().unwrap_or(report);
+                return Some(report.trim().to_string());
+            }
+            // UBSan: `file.c:12:5: runtime error: signed integer overflow: ...`
+            let (location, message) = line.split_once(": runtime error: ")?;
+            let message = message.split(':').next().unwrap_or(message);
+            Some(format!("runtime error: {} at {}", message.trim(), location.trim()))
+        })
+        .unwrap_or_else(|| GENERIC_SIGNATURE.to_string());
+
+    let frames: Vec<&str> = stderr
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim_start();
+            if !line.starts_with('#') {
+                return None;
+            }
+            // `#0 0x4f8a1b in decode /src/cgi_decode.c:24:13`
+            let function = line.split_once(" in ")?.1.split_whitespace().next()?;
+            (!RUNTIME_FRAMES.iter().any(|prefix| function.starts_with(prefix))).then_some(function)
+        })
+        .take(SIGNATURE_FRAMES)
+        .collect();
+
+    if frames.is_empty() {
+        kind
+    } else {
+        format!("{} in {}", kind, frames.join(" < "))
+    }
+}
+
+/// Group crashing inputs by the signature of their stderr, largest group first
+pub fn group_crashes<I>(crashes: I) -> Vec<CrashGroup>
+where
+    I: IntoIterator<Item = (PathBuf, String)>,
+{
+    let mut groups: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    for (input, stderr) in crashes {
+        groups.entry(crash_signature(&stderr)).or_default().push(input);
+    }
+    let mut groups: Vec<CrashGroup> = groups
+        .into_iter()
+        .map(|(signature, inputs)| CrashGroup { signature, inputs })
+        .collect();
+    groups.sort_by_key(|group| std::cmp::Reverse(group.inputs.len()));
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ASAN_REPORT: &str = "\
+=================================================================
+==4242==ERROR: AddressSanitizer: heap-buffer-overflow on address 0x602000000015 at pc 0x4f8a1b
+READ of size 1 at 0x602000000015 thread T0
+    #0 0x4f8a1b in decode /src/cgi_decode.c:24:13
+    #1 0x4f8c2d in parse /src/cgi_decode.c:51:5
+    #2 0x4f8d3e in main /src/cgi_decode.c:70:12
+    #3 0x7f0a in __libc_start_main
+";
+
+    #[test]
+    fn test_asan_signature() {
+        assert_eq!(
+            crash_signature(ASAN_REPORT),
+            "AddressSanitizer: heap-buffer-overflow in decode < parse < main"
+        );
+    }
+
+    #[test]
+    fn test_ubsan_signature() {
+        let stderr = "/src/a.c:12:5: runtime error: signed integer overflow: 2147483647 + 1 cannot be represented in type 'int'\n    #0 0x1 in __ubsan_handle_add_overflow\n    #1 0x2 in add /src/a.c:12:5\n";
+        assert_eq!(
+            crash_signature(stderr),
+            "runtime error: signed integer overflow at /src/a.c:12:5 in add"
+        );
+        assert_eq!(crash_signature("Segmentation fault\n"), "crash");
+    }
+
+    #[test]
+    fn test_group_crashes() {
+        let other = ASAN_REPORT.replace("#1 0x4f8c2d in parse", "#1 0x4f8c2d in unescape");
+        let groups = group_crashes(vec![
+            (PathBuf::from("a"), ASAN_REPORT.to_string()),
+            (PathBuf::from("b"), other),
+            (PathBuf::from("c"), ASAN_REPORT.to_string()),
+        ]);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].inputs, vec![PathBuf::from("a"), PathBuf::from("c")]);
+        assert!(groups[1].signature.contains("unescape"));
+    }
+}